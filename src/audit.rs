@@ -0,0 +1,383 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use chrono::Utc;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::AUDIT_LONG_LIVED_SECS;
+use crate::cookie::Cookie;
+use crate::cookie_db::CookieDB;
+use crate::util::registrable_domain;
+
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Html,
+}
+
+/// The owner of a tracker domain
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tracker {
+    pub company: String,
+    pub category: String,
+}
+
+/// Tracker domains read from a list in the format of Disconnect's
+/// `services.json`:
+///     {"categories": {"Advertising": [
+///         {"Google": {"http://www.google.com/": ["doubleclick.net", ...]}}
+///     ]}}
+#[derive(Debug, Default)]
+pub struct TrackerList {
+    domains: HashMap<String, Tracker>,
+}
+
+impl TrackerList {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_slice(&content)
+            .map_err(|e| e.to_string())
+            .and_then(|json| Self::parse(&json))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn parse(json: &Value) -> Result<Self, String> {
+        let Some(categories) = json["categories"].as_object() else {
+            return Err("Not a Disconnect tracker list".to_string());
+        };
+        let mut domains = HashMap::new();
+        for (category, services) in categories {
+            let services = services.as_array().into_iter().flatten();
+            for (company, urls) in
+                services.filter_map(|s| s.as_object()).flatten()
+            {
+                // Besides the URLs of the company, a service can have
+                // flags such as `"performance": "true"`
+                let urls = urls.as_object().into_iter().flatten();
+                for domain in urls
+                    .filter_map(|(_, d)| d.as_array())
+                    .flatten()
+                    .filter_map(|d| d.as_str())
+                {
+                    domains.insert(
+                        domain.to_lowercase(),
+                        Tracker {
+                            company: company.to_owned(),
+                            category: category.to_owned(),
+                        },
+                    );
+                }
+            }
+        }
+        Ok(TrackerList { domains })
+    }
+
+    /// The tracker domain that a host belongs to, either the
+    /// host itself or one of its parent domains
+    pub fn lookup(&self, host: &str) -> Option<(&str, &Tracker)> {
+        let host = host.trim_start_matches('.').to_lowercase();
+        let mut domain = host.as_str();
+        loop {
+            if let Some((d, t)) = self.domains.get_key_value(domain) {
+                return Some((d.as_str(), t));
+            }
+            domain = domain.split_once('.')?.1;
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DomainCount {
+    pub domain: String,
+    pub cookies: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrackerMatch {
+    pub domain: String,
+    pub company: String,
+    pub category: String,
+    pub cookies: usize,
+}
+
+/// A cookie that is reported, values are left out of the report
+#[derive(Debug, Serialize)]
+pub struct Finding {
+    pub host: String,
+    pub name: String,
+    pub path: String,
+    pub expiry: String,
+}
+
+impl Finding {
+    fn new(cookie: &Cookie) -> Self {
+        Finding {
+            host: cookie.host.to_owned(),
+            name: cookie.name.to_owned(),
+            path: cookie.path.to_owned(),
+            expiry: cookie.match_field("Expiry", false),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileReport {
+    pub profile: String,
+    pub path: String,
+    pub cookies: usize,
+    /// Cookies per registrable domain, most cookies first
+    pub domains: Vec<DomainCount>,
+    /// Unset if no tracker list was given
+    pub trackers: Option<Vec<TrackerMatch>>,
+    /// Cookies that expire more than a year from now
+    pub long_lived: Vec<Finding>,
+    /// Cookies that are sent in cross-site requests over plain HTTP
+    pub samesite_none_insecure: Vec<Finding>,
+    /// Cookies that look like session cookies and are readable by scripts
+    pub session_names_not_http_only: Vec<Finding>,
+}
+
+impl ProfileReport {
+    /// Audit the loaded cookies of a database
+    pub fn new(cdb: &CookieDB, trackers: Option<&TrackerList>) -> Self {
+        let now = Utc::now().timestamp();
+        let mut domains: BTreeMap<String, usize> = BTreeMap::new();
+        for c in cdb.cookies.iter() {
+            *domains.entry(registrable_domain(&c.host)).or_default() += 1;
+        }
+        let mut domains: Vec<DomainCount> = domains
+            .into_iter()
+            .map(|(domain, cookies)| DomainCount { domain, cookies })
+            .collect();
+        domains.sort_by_key(|d| std::cmp::Reverse(d.cookies));
+
+        let trackers = trackers.map(|list| {
+            let mut matches: BTreeMap<&str, (&Tracker, usize)> =
+                BTreeMap::new();
+            for (domain, tracker) in
+                cdb.cookies.iter().filter_map(|c| list.lookup(&c.host))
+            {
+                matches.entry(domain).or_insert((tracker, 0)).1 += 1;
+            }
+            matches
+                .into_iter()
+                .map(|(domain, (tracker, cookies))| TrackerMatch {
+                    domain: domain.to_string(),
+                    company: tracker.company.to_owned(),
+                    category: tracker.category.to_owned(),
+                    cookies,
+                })
+                .collect()
+        });
+
+        let findings = |check: &dyn Fn(&Cookie) -> bool| -> Vec<Finding> {
+            cdb.cookies
+                .iter()
+                .filter(|c| check(c))
+                .map(Finding::new)
+                .collect()
+        };
+        ProfileReport {
+            profile: cdb.display_name(),
+            path: cdb.path_short(),
+            cookies: cdb.cookies.len(),
+            domains,
+            trackers,
+            long_lived: findings(&|c| c.expiry > now + AUDIT_LONG_LIVED_SECS),
+            samesite_none_insecure: findings(&|c| {
+                c.is_samesite_none_insecure()
+            }),
+            session_names_not_http_only: findings(&|c| {
+                !c.http_only && c.has_session_name()
+            }),
+        }
+    }
+
+    /// The sections with individual cookies, as titles and findings
+    fn sections(&self) -> [(&'static str, &Vec<Finding>); 3] {
+        [
+            ("Expiring in more than a year", &self.long_lived),
+            ("SameSite=None without Secure", &self.samesite_none_insecure),
+            (
+                "Session-like names without HttpOnly",
+                &self.session_names_not_http_only,
+            ),
+        ]
+    }
+}
+
+pub fn report_text(reports: &[ProfileReport]) -> String {
+    let mut out = String::new();
+    for report in reports {
+        out += &format!("{}  {}\n", report.profile, report.path);
+        out += &format!(
+            "  {} cookies from {} domains\n",
+            report.cookies,
+            report.domains.len()
+        );
+        let width = report.domains.iter().map(|d| d.domain.len()).max();
+        for d in report.domains.iter() {
+            out += &format!(
+                "    {:<w$}  {}\n",
+                d.domain,
+                d.cookies,
+                w = width.unwrap_or(0)
+            );
+        }
+        match &report.trackers {
+            Some(trackers) => {
+                out += &format!("  Trackers ({})\n", trackers.len());
+                for t in trackers {
+                    out += &format!(
+                        "    {}  {} cookies, {} ({})\n",
+                        t.domain, t.cookies, t.company, t.category
+                    );
+                }
+            }
+            None => out += "  Trackers: no tracker list given (--trackers)\n",
+        }
+        for (title, findings) in report.sections() {
+            out += &format!("  {title} ({})\n", findings.len());
+            for f in findings {
+                out += &format!(
+                    "    {} {} {} {}\n",
+                    f.host, f.name, f.path, f.expiry
+                );
+            }
+        }
+        out += "\n";
+    }
+    out
+}
+
+pub fn report_json(reports: &[ProfileReport]) -> String {
+    serde_json::to_string_pretty(reports).unwrap_or_default()
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A table with a header row, all cells are escaped
+fn html_table(header: &[&str], rows: Vec<Vec<String>>) -> String {
+    let row = |cells: Vec<String>, tag: &str| -> String {
+        let cells: String = cells
+            .iter()
+            .map(|c| format!("<{tag}>{}</{tag}>", html_escape(c)))
+            .collect();
+        format!("<tr>{cells}</tr>\n")
+    };
+    let mut table = String::from("<table>\n");
+    table += &row(header.iter().map(|h| h.to_string()).collect(), "th");
+    for cells in rows {
+        table += &row(cells, "td");
+    }
+    table + "</table>\n"
+}
+
+/// A standalone HTML page
+pub fn report_html(reports: &[ProfileReport]) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Cookie audit</title>\n<style>\n\
+         body { font-family: sans-serif; margin: 2em; }\n\
+         table { border-collapse: collapse; margin-bottom: 1em; }\n\
+         th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; \
+         text-align: left; }\n\
+         </style>\n</head>\n<body>\n<h1>Cookie audit</h1>\n",
+    );
+    for report in reports {
+        out += &format!(
+            "<section>\n<h2>{}</h2>\n<p>{} &middot; {} cookies from {} \
+             domains</p>\n",
+            html_escape(&report.profile),
+            html_escape(&report.path),
+            report.cookies,
+            report.domains.len()
+        );
+        out += "<h3>Domains</h3>\n";
+        out += &html_table(
+            &["Domain", "Cookies"],
+            report
+                .domains
+                .iter()
+                .map(|d| vec![d.domain.to_owned(), d.cookies.to_string()])
+                .collect(),
+        );
+        out += "<h3>Trackers</h3>\n";
+        match &report.trackers {
+            Some(trackers) => {
+                out += &html_table(
+                    &["Domain", "Cookies", "Company", "Category"],
+                    trackers
+                        .iter()
+                        .map(|t| {
+                            vec![
+                                t.domain.to_owned(),
+                                t.cookies.to_string(),
+                                t.company.to_owned(),
+                                t.category.to_owned(),
+                            ]
+                        })
+                        .collect(),
+                );
+            }
+            None => out += "<p>No tracker list given</p>\n",
+        }
+        for (title, findings) in report.sections() {
+            out += &format!("<h3>{} ({})</h3>\n", title, findings.len());
+            out += &html_table(
+                &["Host", "Name", "Path", "Expiry"],
+                findings
+                    .iter()
+                    .map(|f| {
+                        vec![
+                            f.host.to_owned(),
+                            f.name.to_owned(),
+                            f.path.to_owned(),
+                            f.expiry.to_owned(),
+                        ]
+                    })
+                    .collect(),
+            );
+        }
+        out += "</section>\n";
+    }
+    out + "</body>\n</html>\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::audit::TrackerList;
+
+    #[test]
+    fn test_trackers() {
+        let list = TrackerList::parse(&json!({
+            "license": "...",
+            "categories": {
+                "Advertising": [{
+                    "Google": {
+                        "http://www.google.com/": ["doubleclick.net"],
+                        "performance": "true"
+                    }
+                }]
+            }
+        }))
+        .unwrap();
+        let (domain, tracker) = list.lookup(".stats.doubleclick.net").unwrap();
+        assert_eq!(domain, "doubleclick.net");
+        assert_eq!(tracker.company, "Google");
+        assert_eq!(tracker.category, "Advertising");
+        assert!(list.lookup("notdoubleclick.net").is_none());
+        assert!(TrackerList::parse(&json!([])).is_err());
+    }
+}
@@ -0,0 +1,289 @@
+use serde::Deserialize;
+
+use crate::util::DbType;
+
+/// A browser whose cookie databases can be discovered, either built-in
+/// or declared in the config file:
+///
+/// ```toml
+/// [[browsers]]
+/// name = "Vivaldi"
+/// type = "chromium"
+/// search_dirs = [".config/vivaldi"]
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Browser {
+    pub name: String,
+
+    /// The database format used by the browser
+    #[serde(rename = "type")]
+    pub typing: DbType,
+
+    /// Directories to search for cookie databases, relative to $HOME.
+    /// Glob patterns such as `.var/app/*/.mozilla/firefox` are expanded.
+    pub search_dirs: Vec<String>,
+
+    /// Filenames of cookie databases, defaults to the standard
+    /// names for the browser type if left empty
+    #[serde(default)]
+    pub db_names: Vec<String>,
+
+    /// How many directories below a search directory to look for
+    /// databases, defaults to the profile layout of the browser type
+    pub max_depth: Option<usize>,
+}
+
+/// Compile-time representation of a `Browser`
+struct BuiltinBrowser {
+    name: &'static str,
+    typing: DbType,
+    search_dirs: &'static [&'static str],
+    /// Directories of the browser inside of the data directory of a
+    /// flatpak or snap (see `SANDBOX_DIRS`). Flatpaks keep the files
+    /// from `~/.config` in `config`, snaps in `.config` or `common`.
+    sandbox_dirs: &'static [&'static str],
+}
+
+/// The data directories of flatpak and snap installs, relative to $HOME.
+/// Apps are not matched on their id, which differs between packages.
+const SANDBOX_DIRS: &[&str] =
+    &[".var/app/*", "snap/*/common", "snap/*/current"];
+
+/// Search directories of the built-in browsers. Each browser includes
+/// the default Linux, macOS and Windows locations together with
+/// the location within flatpak and snap installs.
+const BUILTIN_BROWSERS: &[BuiltinBrowser] = &[
+    //== Firefox based ==//
+    BuiltinBrowser {
+        name: "Firefox",
+        typing: DbType::Firefox,
+        search_dirs: &[
+            ".mozilla/firefox",
+            "Library/Application Support/Firefox/Profiles",
+            "AppData/Roaming/Mozilla/Firefox/Profiles",
+        ],
+        sandbox_dirs: &[".mozilla/firefox"],
+    },
+    BuiltinBrowser {
+        name: "LibreWolf",
+        typing: DbType::Firefox,
+        search_dirs: &[
+            ".librewolf",
+            "Library/Application Support/librewolf/Profiles",
+            "AppData/Roaming/librewolf/Profiles",
+        ],
+        sandbox_dirs: &[".librewolf"],
+    },
+    BuiltinBrowser {
+        name: "Waterfox",
+        typing: DbType::Firefox,
+        search_dirs: &[
+            ".waterfox",
+            "Library/Application Support/Waterfox/Profiles",
+            "AppData/Roaming/Waterfox/Profiles",
+        ],
+        sandbox_dirs: &[".waterfox"],
+    },
+    BuiltinBrowser {
+        name: "Floorp",
+        typing: DbType::Firefox,
+        search_dirs: &[
+            ".floorp",
+            "Library/Application Support/Floorp/Profiles",
+            "AppData/Roaming/Floorp/Profiles",
+        ],
+        sandbox_dirs: &[".floorp"],
+    },
+    //== WebKit ==//
+    BuiltinBrowser {
+        name: "Safari",
+        typing: DbType::Safari,
+        search_dirs: &[
+            "Library/Containers/com.apple.Safari/Data/Library/Cookies",
+            "Library/Cookies",
+        ],
+        sandbox_dirs: &[],
+    },
+    //== Chromium based ==//
+    BuiltinBrowser {
+        name: "Chromium",
+        typing: DbType::Chrome,
+        search_dirs: &[
+            ".config/chromium",
+            "Library/Application Support/Chromium",
+            "AppData/Local/Chromium/User Data",
+        ],
+        sandbox_dirs: &["config/chromium", ".config/chromium", "chromium"],
+    },
+    BuiltinBrowser {
+        name: "Chrome",
+        typing: DbType::Chrome,
+        search_dirs: &[
+            ".config/google-chrome",
+            "Library/Application Support/Google/Chrome",
+            "AppData/Local/Google/Chrome/User Data",
+        ],
+        sandbox_dirs: &["config/google-chrome", ".config/google-chrome"],
+    },
+    BuiltinBrowser {
+        name: "Brave",
+        typing: DbType::Chrome,
+        search_dirs: &[
+            ".config/BraveSoftware/Brave-Browser",
+            "Library/Application Support/BraveSoftware/Brave-Browser",
+            "AppData/Local/BraveSoftware/Brave-Browser/User Data",
+        ],
+        sandbox_dirs: &[
+            "config/BraveSoftware/Brave-Browser",
+            ".config/BraveSoftware/Brave-Browser",
+        ],
+    },
+    BuiltinBrowser {
+        name: "Vivaldi",
+        typing: DbType::Chrome,
+        search_dirs: &[
+            ".config/vivaldi",
+            "Library/Application Support/Vivaldi",
+            "AppData/Local/Vivaldi/User Data",
+        ],
+        sandbox_dirs: &["config/vivaldi", ".config/vivaldi"],
+    },
+    BuiltinBrowser {
+        name: "Opera",
+        typing: DbType::Chrome,
+        search_dirs: &[
+            ".config/opera",
+            "Library/Application Support/com.operasoftware.Opera",
+            "AppData/Roaming/Opera Software/Opera Stable",
+        ],
+        sandbox_dirs: &["config/opera", ".config/opera"],
+    },
+    BuiltinBrowser {
+        name: "Edge",
+        typing: DbType::Chrome,
+        search_dirs: &[
+            ".config/microsoft-edge",
+            "Library/Application Support/Microsoft Edge",
+            "AppData/Local/Microsoft/Edge/User Data",
+        ],
+        sandbox_dirs: &["config/microsoft-edge", ".config/microsoft-edge"],
+    },
+];
+
+impl Browser {
+    /// The cookie database filenames to look for
+    pub fn db_names(&self) -> Vec<String> {
+        if !self.db_names.is_empty() {
+            return self.db_names.clone();
+        }
+        let db_names: &[&str] = match self.typing {
+            DbType::Firefox => &["cookies.sqlite"],
+            DbType::Chrome => &["Cookies", "Safe Browsing Cookies"],
+            DbType::Safari => &["Cookies.binarycookies"],
+            DbType::Unknown => &[],
+        };
+        db_names.iter().map(|n| n.to_string()).collect()
+    }
+
+    /// The maximum depth of a database below a search directory, e.g.
+    /// `<profile>/cookies.sqlite` for Firefox and
+    /// `<profile>/Network/Cookies` for Chromium
+    pub fn walk_depth(&self) -> usize {
+        self.max_depth.unwrap_or(match self.typing {
+            DbType::Firefox => 2,
+            DbType::Chrome => 3,
+            DbType::Safari => 1,
+            DbType::Unknown => 3,
+        })
+    }
+
+    /// All browsers that are supported out of the box
+    pub fn builtin() -> Vec<Browser> {
+        BUILTIN_BROWSERS
+            .iter()
+            .map(|b| {
+                let sandboxed = SANDBOX_DIRS.iter().flat_map(|sandbox| {
+                    b.sandbox_dirs.iter().map(move |d| format!("{sandbox}/{d}"))
+                });
+                Browser {
+                    name: b.name.to_string(),
+                    typing: b.typing.clone(),
+                    search_dirs: b
+                        .search_dirs
+                        .iter()
+                        .map(|d| d.to_string())
+                        .chain(sandboxed)
+                        .collect(),
+                    db_names: vec![],
+                    max_depth: None,
+                }
+            })
+            .collect()
+    }
+
+    /// Merge browsers from the config file with the built-in ones.
+    /// A configured browser with the same name (case insensitive) as a
+    /// built-in browser replaces it, other configured browsers are added.
+    pub fn merge(
+        builtin: Vec<Browser>,
+        configured: Vec<Browser>,
+    ) -> Vec<Browser> {
+        let mut browsers: Vec<Browser> = builtin
+            .into_iter()
+            .filter(|b| {
+                !configured
+                    .iter()
+                    .any(|c| c.name.eq_ignore_ascii_case(&b.name))
+            })
+            .collect();
+        browsers.extend(configured);
+        browsers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use crate::browser::Browser;
+    use crate::util::DbType;
+
+    #[test]
+    fn test_merge() {
+        #[derive(Deserialize)]
+        struct ConfigFile {
+            browsers: Vec<Browser>,
+        }
+        let config_file: ConfigFile = toml::from_str(
+            r#"
+            [[browsers]]
+            name = "firefox"
+            type = "firefox"
+            search_dirs = [".librewolf"]
+
+            [[browsers]]
+            name = "Vivaldi"
+            type = "chromium"
+            search_dirs = [".config/vivaldi"]
+            "#,
+        )
+        .unwrap();
+
+        let browsers =
+            Browser::merge(Browser::builtin(), config_file.browsers);
+        let firefox: Vec<&Browser> = browsers
+            .iter()
+            .filter(|b| b.name.eq_ignore_ascii_case("firefox"))
+            .collect();
+        assert_eq!(firefox.len(), 1);
+        assert_eq!(firefox[0].search_dirs, vec![".librewolf"]);
+
+        let vivaldi = browsers.iter().find(|b| b.name == "Vivaldi").unwrap();
+        assert_eq!(vivaldi.typing, DbType::Chrome);
+        assert_eq!(
+            vivaldi.db_names(),
+            vec!["Cookies", "Safe Browsing Cookies"]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::path::Path;
+
+use crate::config::Config;
+use crate::cookie::Cookie;
+use crate::decrypt::{plaintext_value, Platform, ValueDecryptor};
+use crate::error::CookieError;
+use crate::store::{Capabilities, CookieStore, Schema, SqliteTable};
+
+/// The `Cookies` database of a Chromium based browser
+pub struct ChromiumStore {
+    table: SqliteTable,
+    /// The name of the browser, used to look up its password
+    browser: String,
+}
+
+impl ChromiumStore {
+    pub fn new(path: &Path, browser: &str) -> Self {
+        ChromiumStore {
+            table: SqliteTable {
+                path: path.to_path_buf(),
+                table: "cookies",
+                field_idx: 0,
+                // Timestamps are stored as microseconds since Jan 01 1601
+                epoch_offset: 11_644_473_600,
+                seconds_fields: &[],
+                encrypted_column: Some("encrypted_value"),
+                version_query: "SELECT value FROM meta WHERE key = 'version';",
+            },
+            browser: browser.to_string(),
+        }
+    }
+
+    /// Decrypt the `encrypted_value` of each cookie if a key or password
+    /// is available for the platform of the database. The password store
+    /// is only consulted for Linux profiles.
+    fn decrypt_values(&self, cookies: &mut [Cookie], db_version: i64) {
+        let cfg = Config::global();
+        let platform = Platform::from_path(&self.table.path);
+        let provider = match platform {
+            Platform::Linux => cfg
+                .chromium_password
+                .as_deref()
+                .or(cfg.password_store.as_deref()),
+            _ => cfg.chromium_password.as_deref(),
+        };
+        let mut decryptor = ValueDecryptor::new(
+            platform,
+            &self.browser,
+            cfg.chromium_key,
+            provider,
+        );
+
+        for c in cookies.iter_mut() {
+            if !c.value.is_empty() || c.encrypted_value.is_empty() {
+                continue;
+            }
+            if let Some(plaintext) = decryptor.decrypt(&c.encrypted_value) {
+                c.value = plaintext_value(&plaintext, db_version);
+            }
+        }
+    }
+
+    /// Chromium leaves `value` empty for encrypted cookies, the
+    /// decrypted value must not end up on disk next to the ciphertext
+    fn without_plaintext(cookies: &[Cookie]) -> Vec<Cookie> {
+        cookies
+            .iter()
+            .cloned()
+            .map(|mut c| {
+                if !c.encrypted_value.is_empty() {
+                    c.value.clear();
+                }
+                c
+            })
+            .collect()
+    }
+}
+
+impl CookieStore for ChromiumStore {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            writable: true,
+            encrypted: true,
+        }
+    }
+
+    fn schema(&self) -> Result<Schema, CookieError> {
+        self.table.schema()
+    }
+
+    fn load(&self) -> Result<Vec<Cookie>, CookieError> {
+        let (mut cookies, version) = self.table.load()?;
+        self.decrypt_values(&mut cookies, version.unwrap_or(0));
+        Ok(cookies)
+    }
+
+    fn load_encrypted(&self) -> Result<Vec<Cookie>, CookieError> {
+        self.table.load().map(|(cookies, _)| cookies)
+    }
+
+    fn insert(&self, cookies: &[Cookie]) -> Result<(), CookieError> {
+        self.table.insert(&Self::without_plaintext(cookies))
+    }
+
+    fn update(&self, cookies: &[Cookie]) -> Result<(), CookieError> {
+        self.table.update(&Self::without_plaintext(cookies))
+    }
+
+    fn delete(&self, cookies: &[Cookie]) -> Result<(), CookieError> {
+        self.table.delete(cookies)
+    }
+
+    fn sql_select(
+        &self,
+        conn: &rusqlite::Connection,
+        schema: &str,
+    ) -> Result<Option<String>, CookieError> {
+        self.table.sql_select(conn, schema).map(Some)
+    }
+}
@@ -1,6 +1,20 @@
 use clap::{Parser, Subcommand};
 use once_cell::sync::OnceCell;
 use phf::phf_map;
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::audit::ReportFormat;
+use crate::browser::Browser;
+use crate::decrypt::parse_key_hex;
+use crate::expr::FilterExpr;
+use crate::key_provider::{
+    EnvPassword, FilePassword, KeyProvider, PasswordStore, StaticPassword,
+};
+use crate::filter::{CookieFilter, ProfileFilter};
+use crate::sort::SortKey;
+use crate::sql::OutputFormat;
 
 //== Global constants ==//
 pub const ENCRYPTED_VALUE: &'static str = "********";
@@ -11,26 +25,76 @@ pub const TUI_PRIMARY_COLOR: u8 = 111;
 pub const TUI_TEXT_TRUNCATE_LIM: usize = 48;
 pub const TUI_SEARCH: &'static str = "Search:";
 pub const SQLITE_FILE_ID: &'static str = "SQLite format 3";
-
-pub const DB_NAMES: &'static [&'static str] =
-    &["Cookies", "Safe Browsing Cookies", "cookies.sqlite"];
-
-pub const SEARCH_DIRS: &'static [&'static str] = &[
-    ".mozilla/firefox",
-    ".config/chromium",
-    ".config/BraveSoftware/Brave-Browser",
-    "Library/Application Support/Firefox/Profiles",
-    "Library/Application Support/Chromium",
-    "Library/Application Support/BraveSoftware/Brave-Browser",
-    "AppData/Roaming/Mozilla/Firefox/Profiles",
-    "Library/Application Support/Firefox",
-    "Library/Application Support/Chromium",
-    "Library/Application Support/BraveSoftware/Brave-Browser",
+/// Files that SQLite keeps next to a database with uncommitted changes
+pub const SQLITE_SIDE_FILES: &[&str] = &["-wal", "-journal"];
+/// Milliseconds to wait after a change to a database before reloading it
+pub const RELOAD_DELAY_MS: u64 = 500;
+pub const CONTAINERS_FILE: &str = "containers.json";
+pub const CONFIG_FILE: &str = "cookiecutter/config.toml";
+pub const DISCOVERY_CACHE: &str = "cookiecutter/discovery.json";
+pub const SNAPSHOT_DIR: &str = "cookiecutter/snapshots";
+/// Cookies that expire further in the future than this are reported
+/// as long-lived by `audit`
+pub const AUDIT_LONG_LIVED_SECS: i64 = 365 * 24 * 60 * 60;
+/// Parts of cookie names that suggest a session or login cookie,
+/// these should not be readable by scripts
+pub const SESSION_COOKIE_NAMES: &[&str] =
+    &["sess", "sid", "auth", "token", "jwt", "login"];
+/// Cookies that outlive this are reported by `lint`, RFC 6265bis limits
+/// the lifetime of cookies to 400 days
+pub const LINT_MAX_LIFETIME_SECS: i64 = 400 * 24 * 60 * 60;
+/// Second-level labels that country code TLDs register domains below,
+/// e.g. `example.co.uk`
+pub const COUNTRY_SECOND_LEVEL_DOMAINS: &[&str] =
+    &["ac", "co", "com", "edu", "gov", "ne", "net", "or", "org"];
+/// Directories inside of profiles that never contain a cookie database,
+/// these are skipped during discovery
+pub const SKIPPED_DIRS: &[&str] = &[
+    "cache2",
+    "storage",
+    "Service Worker",
+    "Cache",
+    "Code Cache",
+    "GPUCache",
+    "IndexedDB",
+    "Extensions",
 ];
+pub const FIREFOX_PROFILES_INI: &str = "profiles.ini";
+pub const FIREFOX_INSTALLS_INI: &str = "installs.ini";
+/// Session files of a Firefox profile, most recent first. The recovery file
+/// is written while Firefox is running and the other one on shutdown.
+pub const FIREFOX_SESSION_FILES: &[&str] =
+    &["sessionstore-backups/recovery.jsonlz4", "sessionstore.jsonlz4"];
+pub const MOZLZ4_MAGIC: &[u8] = b"mozLz40\0";
+pub const SAFARI_MAGIC: &[u8] = b"cook";
+/// Seconds between the UNIX epoch and the Mac epoch (2001-01-01)
+pub const MAC_EPOCH_OFFSET: i64 = 978_307_200;
+pub const CHROMIUM_LOCAL_STATE: &str = "Local State";
+pub const CHROMIUM_V10_PREFIX: &[u8] = b"v10";
+pub const CHROMIUM_V11_PREFIX: &[u8] = b"v11";
+pub const CHROMIUM_GCM_NONCE_LEN: usize = 12;
+pub const CHROMIUM_HOST_DIGEST_LEN: usize = 32;
+pub const CHROMIUM_HOST_DIGEST_VERSION: i64 = 24;
+pub const CHROMIUM_PBKDF2_SALT: &[u8] = b"saltysalt";
+pub const CHROMIUM_MACOS_ITERATIONS: u32 = 1003;
+pub const CHROMIUM_LINUX_ITERATIONS: u32 = 1;
+pub const CHROMIUM_LINUX_V10_PASSWORD: &str = "peanuts";
+pub const CHROMIUM_CBC_IV: [u8; 16] = [b' '; 16];
+pub const SECRET_SERVICE_NAME: &str = "org.freedesktop.secrets";
+pub const SECRET_SERVICE_PATH: &str = "/org/freedesktop/secrets";
+/// Desktops (lowercase $XDG_CURRENT_DESKTOP) where Chromium defaults
+/// to the Secret Service for its password
+pub const LIBSECRET_DESKTOPS: &[&str] =
+    &["gnome", "unity", "pantheon", "x-cinnamon", "xfce", "deepin", "ukui"];
+pub const WSL_OSRELEASE: &str = "/proc/sys/kernel/osrelease";
+pub const WSL_WINDOWS_USERS: &str = "/mnt/c/Users";
+pub const WINDOWS_SHARED_USER_DIRS: &[&str] =
+    &["All Users", "Default", "Default User", "Public"];
 
 /// A constant hash map with keys representing each valid Cookie field.
 /// Each key maps to a tuple that contains the name of the Chrome and
-/// Firefox version of the corresponding field.
+/// Firefox version of the corresponding field. Fields that only exist
+/// in one of the browsers have an empty string for the other.
 pub const COOKIE_FIELDS: phf::Map<&'static str, [&'static str; 2]> = phf_map! {
     "Host"       => ["host_key",         "host"],
     "Name"       => ["name",             "name"],
@@ -42,8 +106,57 @@ pub const COOKIE_FIELDS: phf::Map<&'static str, [&'static str; 2]> = phf_map! {
     "HttpOnly"   => ["is_httponly",      "isHttpOnly"],
     "Secure"     => ["is_secure",        "isSecure"],
     "SameSite"   => ["samesite",         "sameSite"],
+    // Firefox only
+    "OriginAttributes" => ["",           "originAttributes"],
+    "SchemeMap"        => ["",           "schemeMap"],
+    "PartitionedAttributeSet" => ["",    "isPartitionedAttributeSet"],
+    // Chromium only
+    "Priority"        => ["priority",           ""],
+    "SourceScheme"    => ["source_scheme",      ""],
+    "SourcePort"      => ["source_port",        ""],
+    "IsPersistent"    => ["is_persistent",      ""],
+    "HasExpires"      => ["has_expires",        ""],
+    "TopFrameSiteKey" => ["top_frame_site_key", ""],
+    "LastUpdate"      => ["last_update_utc",    ""],
+    // Derived from other columns
+    "Container"       => ["",                   ""],
+    "PartitionKey"    => ["",                   ""],
+    // Set for cookies from a Firefox session file
+    "SessionStore"    => ["",                   ""],
 };
 
+/// Fields that are present for all browsers, in the order they are listed
+pub const COMMON_FIELDS: &[&str] = &[
+    "Host",
+    "Name",
+    "Value",
+    "Path",
+    "Creation",
+    "Expiry",
+    "LastAccess",
+    "HttpOnly",
+    "Secure",
+    "SameSite",
+];
+
+/// Fields that are only present for one of the browsers (or only in
+/// newer schema versions), in the order they are shown in the TUI.
+pub const OPTIONAL_FIELDS: &[&str] = &[
+    "OriginAttributes",
+    "SchemeMap",
+    "PartitionedAttributeSet",
+    "Priority",
+    "SourceScheme",
+    "SourcePort",
+    "IsPersistent",
+    "HasExpires",
+    "TopFrameSiteKey",
+    "LastUpdate",
+    "Container",
+    "PartitionKey",
+    "SessionStore",
+];
+
 //=== CLI arguments ===//
 #[derive(Debug, Subcommand)]
 enum SubArgs {
@@ -64,9 +177,14 @@ enum SubArgs {
         #[clap(short, long, default_value = "Host,Name")]
         fields: String,
 
-        /// Only include entries matching a specific domain name
+        /// Comma separated list of fields to sort the cookies of each
+        /// profile by: host, name, expiry, creation, last_access or size.
+        /// Each field can be followed by `:asc` (default) or `:desc`.
         #[clap(short, long, default_value_t)]
-        domain: String,
+        sort: String,
+
+        #[clap(flatten)]
+        filter: FilterArgs,
     },
     /// Remove cookies non-interactively
     Clean {
@@ -77,9 +195,151 @@ enum SubArgs {
         /// Apply changes
         #[clap(short, long)]
         apply: bool,
+
+        #[clap(flatten)]
+        filter: FilterArgs,
     },
     /// Interactive view of cookies across all browsers
-    Tui {},
+    Tui {
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// Print cookies that are added, changed or removed
+    /// while the browser is running
+    Watch {
+        /// Print each change as a JSON object
+        #[clap(long)]
+        json: bool,
+
+        /// Also reload the databases every <SECONDS>, for filesystems
+        /// that do not report changes (e.g. network mounts)
+        #[clap(long, value_name = "SECONDS")]
+        poll: Option<u64>,
+
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// Save the cookies of the selected profiles, or list saved snapshots
+    Snapshot {
+        #[clap(subcommand)]
+        action: SnapshotArgs,
+    },
+    /// Show cookies that were added, changed or removed between two
+    /// snapshots, or between a snapshot and the current cookies
+    Diff {
+        /// The older snapshot
+        from: String,
+
+        /// The newer snapshot, the current cookies if omitted
+        to: Option<String>,
+
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// Report on the privacy impact of the cookies in each profile
+    Audit {
+        /// Output format of the report
+        #[clap(long, value_enum, default_value_t = ReportFormat::Text)]
+        format: ReportFormat,
+
+        /// Tracker list in the format of Disconnect's `services.json`,
+        /// overrides `trackers` in the config file
+        #[clap(long, value_name = "FILE")]
+        trackers: Option<PathBuf>,
+
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// Check cookie attributes against RFC 6265bis, exits with an
+    /// error if any errors (or with --strict, warnings) are found
+    Lint {
+        /// Also fail on warnings
+        #[clap(long)]
+        strict: bool,
+
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// Summarise the cookies of each profile and browser
+    Stats {
+        /// Number of domains to list by cookie count and value size
+        #[clap(long, default_value_t = 10)]
+        top: usize,
+
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// Run a read-only SQL query against a `cookies` view over all
+    /// selected databases. The view has the columns `browser`, `profile`
+    /// and `db_path` followed by the fields from --list-fields in snake
+    /// case (e.g. `last_access`) with timestamps in UNIX epoch time.
+    /// Each database is also attached as `db<N>`.
+    Sql {
+        query: String,
+
+        /// Output format of the result
+        #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum SnapshotArgs {
+    /// Save the current cookies under a name, replacing
+    /// any earlier snapshot with the same name
+    Save { name: String },
+    /// List saved snapshots
+    List,
+}
+
+/// Cookie filters shared between subcommands
+#[derive(Debug, clap::Args)]
+struct FilterArgs {
+    /// Only include entries matching a specific domain name
+    #[clap(short, long, default_value_t)]
+    domain: String,
+
+    /// Only include cookies from a specific Firefox container,
+    /// given as a name, a `userContextId` or `none`
+    #[clap(short, long, default_value_t)]
+    container: String,
+
+    /// Only include partitioned (CHIPS) cookies
+    #[clap(long, conflicts_with = "no_partitioned")]
+    partitioned: bool,
+
+    /// Only include unpartitioned cookies
+    #[clap(long)]
+    no_partitioned: bool,
+
+    /// Only include cookies partitioned under a specific top-level site,
+    /// e.g. `example.com` or `https://example.com`
+    #[clap(long, default_value_t)]
+    partition_key: String,
+
+    /// Only include cookies matching an expression, e.g.
+    /// `host ~ "google" and expiry < now+7d and not secure`
+    #[clap(long, value_name = "EXPR")]
+    filter: Option<String>,
+}
+
+impl FilterArgs {
+    fn to_filter(&self) -> Result<CookieFilter, String> {
+        let partitioned = if self.partitioned {
+            Some(true)
+        } else if self.no_partitioned {
+            Some(false)
+        } else {
+            None
+        };
+        Ok(CookieFilter {
+            domain: self.domain.clone(),
+            container: self.container.clone(),
+            partitioned,
+            partition_key: self.partition_key.clone(),
+            expr: self.filter.as_deref().map(FilterExpr::parse).transpose()?,
+        })
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -96,37 +356,165 @@ pub struct Args {
     #[clap(short, long)]
     debug: bool,
 
-    /// Only include entries from a specific browser profile.
-    /// Any unique part of the path to profile can be used as an identifier
-    /// e.g. `-p Brave` can be resolved to
-    /// `~/.config/BraveSoftware/Brave-Browser/Default`
-    #[clap(short, long, default_value_t, value_parser)]
-    pub profile: String,
+    /// Only include entries from specific browser profiles, can be repeated.
+    /// Profiles are matched on the name shown in the browser, e.g. `Work`.
+    /// If no profile has a matching name, any unique part of the path to
+    /// the profile can be used as an identifier e.g. `-p Brave` can be
+    /// resolved to `~/.config/BraveSoftware/Brave-Browser/Default`.
+    /// Glob patterns like `-p 'Work*'` are accepted.
+    #[clap(short, long, value_parser)]
+    pub profile: Vec<String>,
+
+    /// Skip entries from specific browser profiles, can be repeated.
+    /// Accepts the same identifiers as --profile.
+    #[clap(short = 'x', long, value_parser)]
+    pub exclude_profile: Vec<String>,
+
+    /// Only include entries from specific browsers (e.g. `firefox`,
+    /// `chromium` or `brave`), can be repeated
+    #[clap(short, long, value_parser)]
+    pub browser: Vec<String>,
 
     /// List valid browser profiles for the --profile option
     #[clap(long, num_args = 0, value_parser)]
     pub list_profiles: bool,
 
-    /// Perform all commands on a supplied cookie database
-    /// (overrides --profile)
-    #[clap(long, short, default_value_t, value_parser)]
-    pub file: String,
+    /// Perform all commands on a supplied cookie database, can be repeated
+    /// and accepts glob patterns. Profiles are only discovered in addition
+    /// to the supplied databases if --profile or --browser is given.
+    #[clap(long, short, value_parser)]
+    pub file: Vec<String>,
+
+    /// Discover profiles below this directory instead of $HOME, e.g. a
+    /// mounted backup disk or a container rootfs. The directory can either
+    /// be a home directory or contain `home/` or `Users/` directories.
+    #[clap(long, env = "COOKIECUTTER_ROOT", value_parser)]
+    pub root: Option<PathBuf>,
+
+    /// Hex encoded AES-256 master key used to decrypt cookie values from
+    /// Windows Chromium profiles, i.e. the DPAPI-unwrapped
+    /// `os_crypt.encrypted_key` from `Local State`
+    #[clap(long, value_parser)]
+    pub chromium_key_hex: Option<String>,
+
+    /// Read the key for --chromium-key-hex from a file, either hex encoded
+    /// or as 32 raw bytes
+    #[clap(long, value_parser, conflicts_with = "chromium_key_hex")]
+    pub chromium_key_file: Option<PathBuf>,
+
+    /// The password used to decrypt cookie values from macOS and Linux
+    /// Chromium profiles, i.e. the "Chrome Safe Storage" password from the
+    /// macOS Keychain or the password that Chromium keeps in the keyring
+    #[clap(long, value_parser)]
+    pub chromium_password: Option<String>,
+
+    /// Read the password for --chromium-password from a file,
+    /// a trailing newline is ignored
+    #[clap(long, value_parser, conflicts_with = "chromium_password")]
+    pub chromium_password_file: Option<PathBuf>,
+
+    /// Read the password for --chromium-password from an environment variable
+    #[clap(
+        long,
+        value_parser,
+        value_name = "VAR",
+        conflicts_with_all = ["chromium_password", "chromium_password_file"]
+    )]
+    pub chromium_password_env: Option<String>,
+
+    /// Where to look up the password of Linux Chromium profiles when no
+    /// --chromium-password is given, named as for Chromium's own option
+    /// [default: auto]
+    #[clap(long, value_enum)]
+    pub password_store: Option<PasswordStore>,
+
+    /// Path to the config file
+    /// [default: $XDG_CONFIG_HOME/cookiecutter/config.toml]
+    #[clap(long, default_value_t, value_parser)]
+    pub config: String,
 
     #[clap(subcommand)]
     subargs: Option<SubArgs>,
 }
 
+//=== Config file ===//
+/// Options that can be set in the config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    /// Browsers to search for in addition to the built-in ones
+    #[serde(default)]
+    browsers: Vec<Browser>,
+
+    /// Default for --password-store
+    password_store: Option<PasswordStore>,
+
+    /// Default for `audit --trackers`
+    trackers: Option<PathBuf>,
+}
+
+impl ConfigFile {
+    /// The config file location, if no path is given explicitly
+    fn default_path() -> Option<PathBuf> {
+        let config_home = match std::env::var("XDG_CONFIG_HOME") {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var("HOME").ok()?).join(".config"),
+        };
+        Some(config_home.join(CONFIG_FILE))
+    }
+
+    /// Parse the config file at `path`, or at the default location if
+    /// `path` is empty. A missing config file at the default location
+    /// is not considered an error.
+    fn load(path: &str) -> Result<Self, String> {
+        let filepath = if path.is_empty() {
+            match Self::default_path() {
+                Some(p) if p.exists() => p,
+                _ => return Ok(ConfigFile::default()),
+            }
+        } else {
+            PathBuf::from(path)
+        };
+        let content = std::fs::read_to_string(&filepath)
+            .map_err(|e| format!("{}: {}", filepath.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("{}: {}", filepath.display(), e))
+    }
+}
+
 //=== Config ===//
-#[derive(Debug)]
 pub struct Config {
     pub err_exit: i32,
     pub debug: bool,
 
+    /// Browsers to search for cookie databases
+    pub browsers: Vec<Browser>,
+
+    /// The discovered profiles to include
+    pub profile_filter: ProfileFilter,
+
+    /// Discover profiles below this directory instead of $HOME
+    pub root: Option<PathBuf>,
+
+    /// Key for decrypting cookie values from Windows Chromium profiles
+    pub chromium_key: Option<[u8; 32]>,
+
+    /// Password for decrypting cookie values from macOS and Linux
+    /// Chromium profiles
+    pub chromium_password: Option<Box<dyn KeyProvider>>,
+
+    /// Keyring to get the password for Linux Chromium profiles from
+    /// if no password was given
+    pub password_store: Option<Box<dyn KeyProvider>>,
+
     // Subcmd: cookies
     pub fields: String,
     pub no_heading: bool,
     pub list_fields: bool,
-    pub domain: String,
+    pub sort: Vec<SortKey>,
+
+    // Subcmd: cookies, clean, tui, watch, diff, audit, lint, stats
+    pub filter: CookieFilter,
 
     // Subcmd: clean
     pub clean: bool,
@@ -135,6 +523,80 @@ pub struct Config {
 
     // Subcmd: tui
     pub tui: bool,
+
+    // Subcmd: watch
+    pub watch: bool,
+    pub json: bool,
+    pub poll: Option<u64>,
+
+    // Subcmd: snapshot
+    pub snapshot_save: Option<String>,
+    pub snapshot_list: bool,
+
+    // Subcmd: diff
+    pub diff_from: Option<String>,
+    pub diff_to: Option<String>,
+
+    // Subcmd: audit
+    pub audit: bool,
+    pub report_format: ReportFormat,
+    pub trackers: Option<PathBuf>,
+
+    // Subcmd: lint
+    pub lint: bool,
+    pub strict: bool,
+
+    // Subcmd: stats
+    pub stats: bool,
+    pub top: usize,
+
+    // Subcmd: sql
+    pub sql: Option<String>,
+    pub sql_format: OutputFormat,
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("err_exit", &self.err_exit)
+            .field("debug", &self.debug)
+            .field("browsers", &self.browsers)
+            .field("profile_filter", &self.profile_filter)
+            .field("root", &self.root)
+            // Never print the key itself
+            .field(
+                "chromium_key",
+                &self.chromium_key.map(|_| "<redacted>"),
+            )
+            .field("chromium_password", &self.chromium_password)
+            .field("password_store", &self.password_store)
+            .field("fields", &self.fields)
+            .field("no_heading", &self.no_heading)
+            .field("list_fields", &self.list_fields)
+            .field("sort", &self.sort)
+            .field("filter", &self.filter)
+            .field("clean", &self.clean)
+            .field("whitelist", &self.whitelist)
+            .field("apply", &self.apply)
+            .field("tui", &self.tui)
+            .field("watch", &self.watch)
+            .field("json", &self.json)
+            .field("poll", &self.poll)
+            .field("snapshot_save", &self.snapshot_save)
+            .field("snapshot_list", &self.snapshot_list)
+            .field("diff_from", &self.diff_from)
+            .field("diff_to", &self.diff_to)
+            .field("audit", &self.audit)
+            .field("report_format", &self.report_format)
+            .field("trackers", &self.trackers)
+            .field("lint", &self.lint)
+            .field("strict", &self.strict)
+            .field("stats", &self.stats)
+            .field("top", &self.top)
+            .field("sql", &self.sql)
+            .field("sql_format", &self.sql_format)
+            .finish()
+    }
 }
 
 impl Default for Config {
@@ -142,12 +604,35 @@ impl Default for Config {
         Config {
             err_exit: 1,
             debug: false,
+            browsers: Browser::builtin(),
+            profile_filter: ProfileFilter::default(),
+            root: None,
+            chromium_key: None,
+            chromium_password: None,
+            password_store: None,
             whitelist: String::from(""),
             no_heading: false,
             fields: String::from(""),
             list_fields: false,
-            domain: String::from(""),
+            sort: vec![],
+            filter: CookieFilter::default(),
             tui: false,
+            watch: false,
+            json: false,
+            poll: None,
+            snapshot_save: None,
+            snapshot_list: false,
+            diff_from: None,
+            diff_to: None,
+            audit: false,
+            report_format: ReportFormat::Text,
+            trackers: None,
+            lint: false,
+            strict: false,
+            stats: false,
+            top: 10,
+            sql: None,
+            sql_format: OutputFormat::Table,
             clean: false,
             apply: false,
         }
@@ -156,36 +641,146 @@ impl Default for Config {
 
 impl Config {
     /// Initialise a new config object from an Args struct
-    pub fn from_args(args: &Args) -> Self {
-        let mut cfg = Config::default();
-        cfg.debug = args.debug;
+    /// and the config file
+    pub fn from_args(args: &Args) -> Result<Self, String> {
+        let config_file = ConfigFile::load(&args.config)?;
+        if let Some(root) = &args.root {
+            if !root.is_dir() {
+                return Err(format!("{}: Not a directory", root.display()));
+            }
+        }
+        let mut cfg = Config {
+            debug: args.debug,
+            browsers: Browser::merge(
+                Browser::builtin(),
+                config_file.browsers,
+            ),
+            profile_filter: ProfileFilter {
+                profiles: args.profile.clone(),
+                exclude_profiles: args.exclude_profile.clone(),
+                browsers: args.browser.clone(),
+            },
+            root: args.root.clone(),
+            chromium_key: Self::chromium_key(args)?,
+            chromium_password: Self::chromium_password(args),
+            password_store: args
+                .password_store
+                .as_ref()
+                .or(config_file.password_store.as_ref())
+                .unwrap_or(&PasswordStore::Auto)
+                .provider(),
+            ..Config::default()
+        };
 
         match &args.subargs {
             Some(SubArgs::Cookies {
                 no_heading,
                 list_fields,
                 fields,
-                domain,
+                sort,
+                filter,
             }) => {
                 cfg.no_heading = *no_heading;
                 cfg.list_fields = *list_fields;
-                cfg.domain = domain.clone();
                 cfg.fields = fields.clone();
-                cfg
+                cfg.sort = SortKey::parse_list(sort)?;
+                cfg.filter = filter.to_filter()?;
             }
-            Some(SubArgs::Clean { whitelist, apply }) => {
+            Some(SubArgs::Clean {
+                whitelist,
+                apply,
+                filter,
+            }) => {
                 cfg.clean = true;
                 cfg.apply = *apply;
                 cfg.whitelist = whitelist.clone();
-                cfg
+                cfg.filter = filter.to_filter()?;
             }
-            Some(SubArgs::Tui {}) => {
+            Some(SubArgs::Tui { filter }) => {
                 cfg.tui = true;
-                cfg
+                cfg.filter = filter.to_filter()?;
             }
-            None => cfg,
+            Some(SubArgs::Watch { json, poll, filter }) => {
+                cfg.watch = true;
+                cfg.json = *json;
+                cfg.poll = *poll;
+                cfg.filter = filter.to_filter()?;
+            }
+            Some(SubArgs::Snapshot { action }) => match action {
+                SnapshotArgs::Save { name } => {
+                    cfg.snapshot_save = Some(name.clone());
+                }
+                SnapshotArgs::List => cfg.snapshot_list = true,
+            },
+            Some(SubArgs::Diff { from, to, filter }) => {
+                cfg.diff_from = Some(from.clone());
+                cfg.diff_to = to.clone();
+                cfg.filter = filter.to_filter()?;
+            }
+            Some(SubArgs::Audit {
+                format,
+                trackers,
+                filter,
+            }) => {
+                cfg.audit = true;
+                cfg.report_format = format.clone();
+                cfg.trackers =
+                    trackers.clone().or(config_file.trackers.clone());
+                cfg.filter = filter.to_filter()?;
+            }
+            Some(SubArgs::Lint { strict, filter }) => {
+                cfg.lint = true;
+                cfg.strict = *strict;
+                cfg.filter = filter.to_filter()?;
+            }
+            Some(SubArgs::Stats { top, filter }) => {
+                cfg.stats = true;
+                cfg.top = *top;
+                cfg.filter = filter.to_filter()?;
+            }
+            Some(SubArgs::Sql { query, format }) => {
+                cfg.sql = Some(query.clone());
+                cfg.sql_format = format.clone();
+            }
+            None => {}
         }
+        Ok(cfg)
     }
+    /// Read the Windows Chromium master key from --chromium-key-hex
+    /// or --chromium-key-file
+    fn chromium_key(args: &Args) -> Result<Option<[u8; 32]>, String> {
+        if let Some(hex) = &args.chromium_key_hex {
+            return parse_key_hex(hex).map(Some);
+        }
+        let Some(path) = &args.chromium_key_file else {
+            return Ok(None);
+        };
+        let content = std::fs::read(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        match <[u8; 32]>::try_from(content.as_slice()) {
+            // Raw keys are exactly 32 bytes, a hex encoded key
+            // is at least 64 bytes
+            Ok(key) => Ok(Some(key)),
+            Err(_) => parse_key_hex(&String::from_utf8_lossy(&content))
+                .map(Some)
+                .map_err(|e| format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    /// The key provider for --chromium-password, --chromium-password-file
+    /// or --chromium-password-env
+    fn chromium_password(args: &Args) -> Option<Box<dyn KeyProvider>> {
+        if let Some(password) = &args.chromium_password {
+            Some(Box::new(StaticPassword(password.to_owned())))
+        } else if let Some(path) = &args.chromium_password_file {
+            Some(Box::new(FilePassword(path.to_owned())))
+        } else {
+            args.chromium_password_env
+                .as_ref()
+                .map(|var| Box::new(EnvPassword(var.to_owned())) as _)
+        }
+    }
+
     /// Used to access the global config object in the program
     pub fn global() -> &'static Self {
         CONFIG
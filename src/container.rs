@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::config::CONTAINERS_FILE;
+
+/// The subset of a Firefox `containers.json` file that we care about
+#[derive(Debug, Deserialize)]
+struct ContainersFile {
+    identities: Vec<Identity>,
+}
+
+/// A Firefox container ("contextual identity").
+/// User created containers have a `name`, the built-in ones only have a
+/// localisation id, e.g. `userContextPersonal.label`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Identity {
+    user_context_id: u32,
+    name: Option<String>,
+    #[serde(rename = "l10nID")]
+    l10n_id: Option<String>,
+}
+
+impl Identity {
+    /// The name shown for the container in the Firefox UI
+    fn display_name(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.to_owned();
+        }
+        match self.l10n_id.as_deref() {
+            Some("userContextPersonal.label") => "Personal".to_string(),
+            Some("userContextWork.label") => "Work".to_string(),
+            Some("userContextBanking.label") => "Banking".to_string(),
+            Some("userContextShopping.label") => "Shopping".to_string(),
+            _ => Self::fallback_name(self.user_context_id),
+        }
+    }
+
+    /// Name used for containers without an entry in `containers.json`
+    fn fallback_name(user_context_id: u32) -> String {
+        format!("Container {user_context_id}")
+    }
+}
+
+/// Map each `userContextId` to a container name from the `containers.json`
+/// file of a Firefox profile. Returns an empty map if the profile has no
+/// (parseable) containers file.
+pub fn load_containers(profile_dir: &Path) -> HashMap<u32, String> {
+    let Ok(content) = fs::read_to_string(profile_dir.join(CONTAINERS_FILE))
+    else {
+        return HashMap::new();
+    };
+    match serde_json::from_str::<ContainersFile>(&content) {
+        Ok(containers) => containers
+            .identities
+            .iter()
+            .map(|i| (i.user_context_id, i.display_name()))
+            .collect(),
+        Err(_) => HashMap::new(),
+    }
+}
+
+/// Resolve the name of a container, falling back to a generic name
+/// for ids that are missing from the profile's `containers.json`
+pub fn container_name(
+    containers: &HashMap<u32, String>,
+    user_context_id: u32,
+) -> String {
+    containers
+        .get(&user_context_id)
+        .cloned()
+        .unwrap_or_else(|| Identity::fallback_name(user_context_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::container::load_containers;
+
+    #[test]
+    fn test_load_containers() {
+        let dir = std::env::temp_dir().join("cookiecutter_test_containers");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("containers.json"),
+            r#"{"version":5,"lastUserContextId":6,"identities":[
+                {"userContextId":1,"public":true,"icon":"fingerprint",
+                 "color":"blue","l10nID":"userContextPersonal.label"},
+                {"userContextId":6,"public":true,"icon":"briefcase",
+                 "color":"red","name":"Client A"}]}"#,
+        )
+        .unwrap();
+
+        let containers = load_containers(&dir);
+        assert_eq!(containers.get(&1).unwrap(), "Personal");
+        assert_eq!(containers.get(&6).unwrap(), "Client A");
+        assert!(!containers.contains_key(&2));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,11 +1,16 @@
 use std::fmt;
 
 use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
 
-use crate::config::ENCRYPTED_VALUE;
-use crate::{ALL_FIELDS, COOKIE_FIELDS};
+use crate::config::{
+    COMMON_FIELDS, COOKIE_FIELDS, ENCRYPTED_VALUE, OPTIONAL_FIELDS,
+    SESSION_COOKIE_NAMES,
+};
+use crate::util::{percent_decode, percent_encode};
+use crate::ALL_FIELDS;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Cookie {
     /// The domain that created the cookie
     pub host: String,
@@ -44,9 +49,238 @@ pub struct Cookie {
 
     /// The encrypted value of a cooke, unique to Chrome
     pub encrypted_value: Vec<u8>,
+
+    //== Browser specific fields ==//
+    // These are `None` when the field does not exist in the
+    // schema of the database that the cookie was loaded from.
+
+    /// Serialised origin attributes, unique to Firefox, e.g.
+    /// `^userContextId=2` for a cookie that belongs to a container
+    pub origin_attributes: Option<String>,
+
+    /// Bitmap of the schemes the cookie has been set from, unique to Firefox:
+    ///     HTTP == 1
+    ///     HTTPS == 2
+    ///     FILE == 4
+    pub scheme_map: Option<i64>,
+
+    /// Set if the cookie was created with the `Partitioned` attribute,
+    /// unique to Firefox
+    pub is_partitioned_attribute_set: Option<bool>,
+
+    /// The `Priority` attribute, unique to Chromium:
+    ///     High == 2
+    ///     Medium == 1
+    ///     Low == 0
+    pub priority: Option<i64>,
+
+    /// The scheme of the URL that set the cookie, unique to Chromium:
+    ///     Secure == 2
+    ///     NonSecure == 1
+    ///     Unset == 0
+    pub source_scheme: Option<i64>,
+
+    /// The port of the URL that set the cookie, unique to Chromium
+    pub source_port: Option<i64>,
+
+    /// Unset for cookies that expire at the end of a session,
+    /// unique to Chromium
+    pub is_persistent: Option<bool>,
+
+    /// Set if the cookie was created with an expiry date,
+    /// unique to Chromium
+    pub has_expires: Option<bool>,
+
+    /// The top-level site that a partitioned cookie is keyed on,
+    /// unique to Chromium. Empty for unpartitioned cookies.
+    pub top_frame_site_key: Option<String>,
+
+    /// The last update timestamp in UNIX epoch time, unique to Chromium
+    pub last_update: Option<i64>,
+
+    /// Name of the Firefox container that the cookie belongs to,
+    /// resolved from `originAttributes` and the profile's `containers.json`.
+    /// Unset for cookies outside of a container.
+    pub container: Option<String>,
+
+    /// Set for session cookies that were read from a Firefox session file
+    /// (`sessionstore.jsonlz4`) rather than from the cookie database.
+    /// These can not be deleted, Firefox restores them from memory.
+    pub from_session_store: bool,
+}
+
+/// The attributes encoded in a Firefox `originAttributes` suffix, e.g.
+/// `^partitionKey=%28https%2Cexample.com%29&userContextId=2`.
+/// Attributes that are not present are left at their default values.
+#[derive(Debug, Default, PartialEq)]
+pub struct OriginAttributes {
+    /// Identifies the container of a cookie, 0 if not in a container
+    pub user_context_id: u32,
+    /// Non-zero for cookies from private browsing windows
+    pub private_browsing_id: u32,
+    /// Set when first-party isolation is enabled
+    pub first_party_domain: String,
+    /// The top-level site of a partitioned cookie, e.g. `(https,example.com)`
+    pub partition_key: String,
+}
+
+impl OriginAttributes {
+    /// Parse a serialised `originAttributes` string, unknown
+    /// attributes are ignored
+    pub fn parse(suffix: &str) -> Self {
+        let mut attrs = OriginAttributes::default();
+        let suffix = suffix.strip_prefix('^').unwrap_or(suffix);
+
+        for pair in suffix.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = percent_decode(value);
+            match key {
+                "userContextId" => {
+                    attrs.user_context_id = value.parse().unwrap_or(0)
+                }
+                "privateBrowsingId" => {
+                    attrs.private_browsing_id = value.parse().unwrap_or(0)
+                }
+                "firstPartyDomain" => attrs.first_party_domain = value,
+                "partitionKey" => attrs.partition_key = value,
+                _ => {}
+            }
+        }
+        attrs
+    }
+
+    /// Serialise the attributes into an `originAttributes` suffix like
+    /// Firefox does, attributes at their default values are left out and
+    /// the remaining ones are sorted by name
+    pub fn suffix(&self) -> String {
+        let mut pairs = vec![];
+        if self.user_context_id != 0 {
+            pairs.push(format!("userContextId={}", self.user_context_id));
+        }
+        if self.private_browsing_id != 0 {
+            pairs.push(format!(
+                "privateBrowsingId={}",
+                self.private_browsing_id
+            ));
+        }
+        if !self.first_party_domain.is_empty() {
+            pairs.push(format!(
+                "firstPartyDomain={}",
+                percent_encode(&self.first_party_domain)
+            ));
+        }
+        if !self.partition_key.is_empty() {
+            pairs.push(format!(
+                "partitionKey={}",
+                percent_encode(&self.partition_key)
+            ));
+        }
+        pairs.sort();
+        if pairs.is_empty() {
+            String::new()
+        } else {
+            format!("^{}", pairs.join("&"))
+        }
+    }
 }
 
 impl Cookie {
+    /// The parsed `originAttributes` of the cookie, always empty
+    /// for cookies from Chromium
+    pub fn origin(&self) -> OriginAttributes {
+        match &self.origin_attributes {
+            Some(suffix) => OriginAttributes::parse(suffix),
+            None => OriginAttributes::default(),
+        }
+    }
+
+    /// The top-level site that a partitioned (CHIPS) cookie is keyed on,
+    /// normalised to `<scheme>://<site>[:<port>]` for both browsers.
+    /// Unset for unpartitioned cookies.
+    pub fn partition_key(&self) -> Option<String> {
+        if let Some(key) = &self.top_frame_site_key {
+            if !key.is_empty() {
+                return Some(key.to_owned());
+            }
+        }
+        // Firefox stores the key as `(<scheme>,<site>[,<port>][,f])`
+        let partition_key = self.origin().partition_key;
+        if partition_key.is_empty() {
+            return None;
+        }
+        let parts: Vec<&str> = partition_key
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .split(',')
+            .collect();
+        match parts.as_slice() {
+            [scheme, site, rest @ ..] => {
+                match rest.iter().find(|p| p.parse::<u16>().is_ok()) {
+                    Some(port) => Some(format!("{scheme}://{site}:{port}")),
+                    None => Some(format!("{scheme}://{site}")),
+                }
+            }
+            _ => Some(partition_key),
+        }
+    }
+
+    /// The size of the value, or of the encrypted value
+    /// if it could not be decrypted
+    pub fn value_size(&self) -> usize {
+        if self.value.is_empty() {
+            self.encrypted_value.len()
+        } else {
+            self.value.len()
+        }
+    }
+
+    /// Whether the name suggests a session or login cookie
+    pub fn has_session_name(&self) -> bool {
+        let name = self.name.to_lowercase();
+        SESSION_COOKIE_NAMES.iter().any(|n| name.contains(n))
+    }
+
+    /// Whether the cookie has only been set from HTTPS URLs, unknown
+    /// for cookies from older databases and Safari
+    pub fn is_https_only(&self) -> bool {
+        // Firefox keeps a bitmap of schemes with HTTPS == 2, Chromium
+        // the scheme of the last URL that set the cookie with Secure == 2
+        self.scheme_map == Some(2) || self.source_scheme == Some(2)
+    }
+
+    /// Whether the cookie is sent on cross-site requests (SameSite=None)
+    /// without being Secure, which browsers reject for new cookies
+    pub fn is_samesite_none_insecure(&self) -> bool {
+        self.samesite == 0 && !self.secure
+    }
+
+    /// Two cookies are the same if they would overwrite each other
+    /// in the browser, i.e. if they share host, name, path, origin
+    /// attributes and partition key.
+    pub fn is_same_cookie(&self, other: &Cookie) -> bool {
+        self.host == other.host
+            && self.name == other.name
+            && self.path == other.path
+            && self.origin_attributes == other.origin_attributes
+            && self.top_frame_site_key == other.top_frame_site_key
+    }
+
+    /// Check if the cookie belongs to a container given either a
+    /// container name (case insensitive), a `userContextId` or
+    /// `none` for cookies outside of any container.
+    pub fn in_container(&self, container: &str) -> bool {
+        let user_context_id = self.origin().user_context_id;
+        if container.eq_ignore_ascii_case("none") {
+            user_context_id == 0
+        } else if let Ok(id) = container.parse::<u32>() {
+            user_context_id == id
+        } else {
+            self.container
+                .as_ref()
+                .is_some_and(|c| c.eq_ignore_ascii_case(container))
+        }
+    }
+
     /// Construct a newline separated string with the specified field names
     /// The `fields` parameter is a comma separated string or `All`
     pub fn fields_as_str(
@@ -54,19 +288,21 @@ impl Cookie {
         fields: &String,
         use_name: bool,
     ) -> String {
-        let mut values: Vec<String> = COOKIE_FIELDS
-            .keys()
-            .map(|f| {
-                // Skip fields not listed in the --fields option
-                if !fields.split(",").any(|s| s == *f || fields == ALL_FIELDS) {
-                    String::from("")
-                } else {
-                    self.match_field(*f, use_name)
-                }
-            })
+        // Fields are listed in the order they were given, or in the
+        // order of `--list-fields` for `All`
+        let field_names: Vec<&str> = if fields == ALL_FIELDS {
+            COMMON_FIELDS.iter().chain(OPTIONAL_FIELDS).copied().collect()
+        } else {
+            fields
+                .split(",")
+                .filter(|f| COOKIE_FIELDS.contains_key(f))
+                .collect()
+        };
+        let values: Vec<String> = field_names
+            .iter()
+            .map(|f| self.match_field(f, use_name))
             .filter(|f| f != "")
             .collect();
-        values.sort();
         values.join("\n")
     }
 
@@ -124,10 +360,90 @@ impl Cookie {
                 };
                 self.field_fmt(use_name, "SameSite", samesite)
             }
+            //== Optional fields, empty if unset ==//
+            "OriginAttributes" => self.opt_field_fmt(
+                use_name,
+                "OriginAttributes",
+                self.origin_attributes.as_ref(),
+            ),
+            "SchemeMap" => self.opt_field_fmt(
+                use_name,
+                "SchemeMap",
+                self.scheme_map.map(Self::scheme_map_fmt),
+            ),
+            "PartitionedAttributeSet" => self.opt_field_fmt(
+                use_name,
+                "PartitionedAttributeSet",
+                self.is_partitioned_attribute_set,
+            ),
+            "Priority" => {
+                let priority = self.priority.map(|p| match p {
+                    2 => "High",
+                    1 => "Medium",
+                    0 => "Low",
+                    _ => "Unknown",
+                });
+                self.opt_field_fmt(use_name, "Priority", priority)
+            }
+            "SourceScheme" => {
+                let scheme = self.source_scheme.map(|s| match s {
+                    2 => "Secure",
+                    1 => "NonSecure",
+                    0 => "Unset",
+                    _ => "Unknown",
+                });
+                self.opt_field_fmt(use_name, "SourceScheme", scheme)
+            }
+            "SourcePort" => {
+                self.opt_field_fmt(use_name, "SourcePort", self.source_port)
+            }
+            "IsPersistent" => self.opt_field_fmt(
+                use_name,
+                "IsPersistent",
+                self.is_persistent,
+            ),
+            "HasExpires" => {
+                self.opt_field_fmt(use_name, "HasExpires", self.has_expires)
+            }
+            "TopFrameSiteKey" => self.opt_field_fmt(
+                use_name,
+                "TopFrameSiteKey",
+                self.top_frame_site_key.as_ref(),
+            ),
+            "LastUpdate" => self.opt_field_fmt(
+                use_name,
+                "LastUpdate",
+                self.last_update.map(Self::date_fmt),
+            ),
+            "Container" => self.opt_field_fmt(
+                use_name,
+                "Container",
+                self.container.as_ref(),
+            ),
+            "PartitionKey" => self.opt_field_fmt(
+                use_name,
+                "PartitionKey",
+                self.partition_key(),
+            ),
+            "SessionStore" => self.opt_field_fmt(
+                use_name,
+                "SessionStore",
+                self.from_session_store.then_some(true),
+            ),
             _ => panic!("Unknown cookie field"),
         }
     }
 
+    /// Comma separated list of the schemes in a Firefox `schemeMap`
+    fn scheme_map_fmt(scheme_map: i64) -> String {
+        let schemes: Vec<&str> = [(1, "http"), (2, "https"), (4, "file")]
+            .iter()
+            .filter(|(bit, _)| scheme_map & bit != 0)
+            .map(|(_, name)| *name)
+            .collect();
+        schemes.join(",")
+    }
+
     fn date_fmt(epoch: i64) -> DateTime<Utc> {
         match Utc.timestamp_opt(epoch, 0) {
             chrono::offset::LocalResult::Single(s) => s,
@@ -149,4 +465,80 @@ impl Cookie {
         }
         output + &value.to_string()
     }
+
+    /// Like `field_fmt()` but returns an empty string for unset fields
+    /// so that they are skipped in the output
+    fn opt_field_fmt<T: fmt::Display>(
+        &self,
+        use_name: bool,
+        name: &'static str,
+        value: Option<T>,
+    ) -> String {
+        match value {
+            Some(value) => self.field_fmt(use_name, name, value),
+            None => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::cookie::{Cookie, OriginAttributes};
+
+    /// A secure `SameSite=Lax` cookie for the path `/` that is outside of
+    /// any container, other fields can be set with the struct update
+    /// syntax, e.g. `Cookie { expiry, ..cookie("example.com", "id", "") }`
+    pub fn cookie(host: &str, name: &str, value: &str) -> Cookie {
+        Cookie {
+            host: host.to_string(),
+            name: name.to_string(),
+            value: value.to_string(),
+            path: "/".to_string(),
+            secure: true,
+            samesite: 1,
+            origin_attributes: Some(String::new()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_origin_attributes() {
+        let attrs = OriginAttributes::parse(
+            "^partitionKey=%28https%2Cexample.com%29&userContextId=2",
+        );
+        assert_eq!(attrs.user_context_id, 2);
+        assert_eq!(attrs.partition_key, "(https,example.com)");
+        assert_eq!(attrs.private_browsing_id, 0);
+        assert_eq!(OriginAttributes::parse(""), OriginAttributes::default());
+        assert_eq!(
+            attrs.suffix(),
+            "^partitionKey=%28https%2Cexample.com%29&userContextId=2"
+        );
+        assert_eq!(OriginAttributes::default().suffix(), "");
+    }
+
+    #[test]
+    fn test_partition_key() {
+        let mut cookie = Cookie {
+            host: ".example.com".to_string(),
+            name: "id".to_string(),
+            value: String::new(),
+            path: "/".to_string(),
+            secure: true,
+            origin_attributes: Some(String::new()),
+            ..Default::default()
+        };
+        assert_eq!(cookie.partition_key(), None);
+
+        cookie.origin_attributes =
+            Some("^partitionKey=%28https%2Csite.org%2C8443%29".to_string());
+        assert_eq!(
+            cookie.partition_key().as_deref(),
+            Some("https://site.org:8443")
+        );
+
+        cookie.origin_attributes = None;
+        cookie.top_frame_site_key = Some("https://site.org".to_string());
+        assert_eq!(cookie.partition_key().as_deref(), Some("https://site.org"));
+    }
 }
@@ -1,15 +1,32 @@
 use std::cmp;
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 
-use crate::config::COOKIE_FIELDS;
 use crate::cookie::Cookie;
+use crate::error::CookieError;
+use crate::filter::CookieFilter;
+use crate::profiles::{
+    chromium_profile_info, firefox_profile_info, ProfileInfo,
+};
+use crate::sort::{cmp_cookies, SortKey};
+use crate::store::{open_store, CookieStore};
 use crate::util::{get_home, DbType};
 
 #[derive(Debug)]
 pub struct CookieDB {
     pub path: std::path::PathBuf,
     pub typing: DbType,
+    /// The name of the browser that the database belongs to
+    pub browser: String,
+    /// Profile metadata from `profiles.ini` or `Local State`,
+    /// unset if the database is not part of a known profile
+    pub profile: Option<ProfileInfo>,
     pub cookies: Vec<Cookie>,
+    /// Set once `cookies` has been read from the database
+    pub loaded: bool,
+    /// Indices into `cookies` for each host, kept in sync
+    /// with `cookies` by every method that modifies it
+    host_index: BTreeMap<String, Vec<usize>>,
 }
 
 //== Enable hashing ==//
@@ -41,237 +58,291 @@ impl Ord for CookieDB {
 
 //== Main impl ==//
 impl CookieDB {
+    /// Create a handle to a cookie database, no cookies are
+    /// loaded until `load_cookies()` is called
+    pub fn new(
+        path: std::path::PathBuf,
+        typing: DbType,
+        browser: &str,
+    ) -> Self {
+        CookieDB {
+            path,
+            typing,
+            browser: browser.to_string(),
+            profile: None,
+            cookies: vec![],
+            loaded: false,
+            host_index: BTreeMap::new(),
+        }
+    }
+
+    /// Read the name of the profile that the database belongs to
+    /// from the browser's profile list
+    pub fn load_profile_info(&mut self) {
+        self.profile = match self.typing {
+            DbType::Firefox => {
+                self.path.parent().and_then(firefox_profile_info)
+            }
+            DbType::Chrome => chromium_profile_info(&self.path),
+            DbType::Safari | DbType::Unknown => None,
+        };
+    }
+
+    /// The browser and profile name (if known) or path of the database
+    pub fn display_name(&self) -> String {
+        match &self.profile {
+            Some(p) if p.is_default => {
+                format!("{}: {} (default)", self.browser, p.name)
+            }
+            Some(p) => format!("{}: {}", self.browser, p.name),
+            None => format!("{}: {}", self.browser, self.path_short()),
+        }
+    }
+
     /// Return the parent of the current path and replaces $HOME with "~".
     /// Returns `path` as is if it is not an absolute path.
     pub fn path_short(&self) -> String {
         if self.path.has_root() {
-            self.path
-                .parent()
-                .unwrap()
-                .to_string_lossy()
-                .replace(&get_home(), "~")
+            let parent = self.path.parent().unwrap();
+            match get_home().and_then(|h| parent.strip_prefix(h).ok()) {
+                Some(rel) => format!("~/{}", rel.to_string_lossy()),
+                None => parent.to_string_lossy().to_string(),
+            }
         } else {
             self.path.to_string_lossy().to_string()
         }
     }
 
-    /// Fetch the name of the cookies table depending on
-    /// the browser type.
-    fn table_name(&self) -> &'static str {
-        if self.typing == DbType::Firefox {
-            "moz_cookies"
-        } else {
-            "cookies"
-        }
+    /// The backend that reads and writes the database
+    pub fn store(&self) -> Result<Box<dyn CookieStore>, CookieError> {
+        open_store(&self.typing, &self.path, &self.browser)
     }
 
-    /// Timestamps are stored internally as UNIX epoch microseconds
-    /// for Firefox and as microseconds since Jan 01 1601 in Chrome
-    ///
-    /// Cookies with a Session-only lifetime will have 0 as their
-    /// expiry date in Chrome
-    fn get_unix_epoch(&self, timestamp: i64) -> i64 {
-        if timestamp == 0 {
-            0
-        } else if self.typing == DbType::Firefox {
-            timestamp / 1_000_000
-        } else {
-            (timestamp / 1_000_000) - 11_644_473_600
-        }
+    /// Databases that can be read but not modified
+    pub fn is_read_only(&self) -> bool {
+        self.store().map_or(true, |s| !s.capabilities().writable)
     }
 
     /// Load all cookies from the current `path` into the `cookies` vector
-    pub fn load_cookies(&mut self) -> Result<(), rusqlite::Error> {
-        let conn = rusqlite::Connection::open_with_flags(&self.path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
-        let field_idx = if self.typing == DbType::Chrome { 0 } else { 1 };
-        let encrypted_field = if self.typing == DbType::Chrome {
-            "encrypted_value"
-        } else {
-            "NULL"
-        };
+    pub fn load_cookies(&mut self) -> Result<(), CookieError> {
+        let cookies = self.store()?.load()?;
+        self.set_cookies(cookies);
+        Ok(())
+    }
 
-        let query = format!(
-            "SELECT {},{},{},{},{},{},{},{},{},{},{} FROM {};",
-            COOKIE_FIELDS["Host"][field_idx],
-            COOKIE_FIELDS["Name"][field_idx],
-            COOKIE_FIELDS["Value"][field_idx],
-            COOKIE_FIELDS["Path"][field_idx],
-            COOKIE_FIELDS["Creation"][field_idx],
-            COOKIE_FIELDS["Expiry"][field_idx],
-            COOKIE_FIELDS["LastAccess"][field_idx],
-            COOKIE_FIELDS["HttpOnly"][field_idx],
-            COOKIE_FIELDS["Secure"][field_idx],
-            COOKIE_FIELDS["SameSite"][field_idx],
-            encrypted_field,
-            self.table_name()
-        );
-        let mut stmt = conn.prepare(&query)?;
-        let results_iter = stmt.query_map([], |row| {
-            // The second parameter to get() denotes
-            // the underlying type that the fetched field is expected to have
-            //
-            // We use .unwrap() to get notified explicitly notified
-            // of parsing failures
-            Ok(Cookie {
-                host: row.get::<_, String>(0).unwrap(),
-                name: row.get::<_, String>(1).unwrap(),
-                value: row.get::<_, String>(2).unwrap(),
-                path: row.get::<_, String>(3).unwrap(),
-                creation: self.get_unix_epoch(row.get::<_, i64>(4).unwrap()),
-                expiry: self.get_unix_epoch(row.get::<_, i64>(5).unwrap()),
-                last_access: self.get_unix_epoch(row.get::<_, i64>(6).unwrap()),
-                http_only: row.get::<_, bool>(7).unwrap(),
-                secure: row.get::<_, bool>(8).unwrap(),
-                samesite: row.get::<_, i32>(9).unwrap(),
-                encrypted_value: row.get::<_, Vec<u8>>(10).unwrap_or(vec![]),
-            })
-        })?;
-
-        // The query_map() call returns an iterator
-        // of results, Ok(), which we need to unwrap
-        // before calling collect
-        self.cookies = results_iter.filter_map(|r| r.ok()).collect();
-
-        if self.typing == DbType::Chrome { /* TODO: decrypt() */ }
-
-        stmt.finalize().unwrap();
-        conn.close().unwrap();
+    /// Load all cookies like `load_cookies()`, but leave encrypted
+    /// values encrypted
+    pub fn load_cookies_encrypted(&mut self) -> Result<(), CookieError> {
+        let cookies = self.store()?.load_encrypted()?;
+        self.set_cookies(cookies);
         Ok(())
     }
 
-    /// Remove all cookies from the underlying database except those
-    /// from a domain within the whitelist
+    /// Replace the loaded cookies, e.g. with those from a reload
+    pub fn set_cookies(&mut self, cookies: Vec<Cookie>) {
+        self.cookies = cookies;
+        self.loaded = true;
+        self.index_hosts();
+    }
+
+    /// Rebuild the host index after `cookies` has changed
+    fn index_hosts(&mut self) {
+        self.host_index.clear();
+        for (i, c) in self.cookies.iter().enumerate() {
+            self.host_index.entry(c.host.to_owned()).or_default().push(i);
+        }
+    }
+
+    /// Drop all loaded cookies that do not match the given filter
+    pub fn retain_matching(&mut self, filter: &CookieFilter) {
+        self.cookies.retain(|c| filter.matches(c));
+        self.index_hosts();
+    }
+
+    /// Sort the loaded cookies by the given keys, cookies that
+    /// compare as equal keep the order of the database
+    pub fn sort_cookies(&mut self, keys: &[SortKey]) {
+        self.cookies.sort_by(|a, b| cmp_cookies(keys, a, b));
+        self.index_hosts();
+    }
+
+    /// Remove all loaded cookies from the underlying database except those
+    /// from a domain within the whitelist. Cookies that should be kept
+    /// regardless of the whitelist (e.g. those outside of a `--container`)
+    /// should be filtered out from `cookies` before calling this.
+    /// Cookies from a session file are never removed.
     pub fn clean(
-        &self,
-        whitelist: &Vec<String>,
+        &mut self,
+        whitelist: &[String],
         apply: bool,
-    ) -> Result<(), rusqlite::Error> {
-        let field_idx = if self.typing == DbType::Chrome { 0 } else { 1 };
-
-        let query = format!(
-            "DELETE FROM {} WHERE {} NOT IN ({});",
-            self.table_name(),
-            COOKIE_FIELDS["Host"][field_idx],
-            whitelist.join(",")
-        );
+    ) -> Result<(), CookieError> {
+        let to_delete: Vec<Cookie> = self
+            .cookies
+            .iter()
+            .filter(|c| !c.from_session_store)
+            .filter(|c| !whitelist.contains(&c.host))
+            .cloned()
+            .collect();
 
         if apply {
-            let conn = rusqlite::Connection::open(&self.path)?;
-            conn.execute(&query, rusqlite::params![])?;
-            conn.close().unwrap();
+            self.delete_cookies(&to_delete)?;
         } else {
-            println!(" * {query}");
+            for c in to_delete.iter() {
+                let mut line = format!(" * {} {}", c.host, c.name);
+                if let Some(container) = &c.container {
+                    line += &format!(" [{container}]");
+                }
+                if let Some(partition_key) = c.partition_key() {
+                    line += &format!(" (partitioned: {partition_key})");
+                }
+                println!("{line}");
+            }
         }
         Ok(())
     }
 
-    /// Delete a cookie with a specific name from a domain or
-    /// ALL cookies from a domain if no name is specified.
-    /// This call updates both the SQLite store and the
-    /// internal `cookies` vector.
-    pub fn delete_from_domain(
+    /// Delete the given cookies from the database. Each cookie is matched
+    /// on its full identity so that cookies with the same name in other
+    /// containers or partitions are left untouched.
+    /// This call updates both the backing store and the
+    /// internal `cookies` vector. Cookies from a session file are skipped.
+    pub fn delete_cookies(
         &mut self,
-        domain: &str,
-        name: &str,
-    ) -> Result<(), rusqlite::Error> {
-        let field_idx = if self.typing == DbType::Chrome { 0 } else { 1 };
-
-        let query = if name.is_empty() {
-            format!(
-                "DELETE FROM {} WHERE {} == \"{}\";",
-                self.table_name(),
-                COOKIE_FIELDS["Host"][field_idx],
-                domain
-            )
-        } else {
-            format!(
-                "DELETE FROM {} WHERE {} == \"{}\" AND {} == \"{}\";",
-                self.table_name(),
-                COOKIE_FIELDS["Host"][field_idx],
-                domain,
-                COOKIE_FIELDS["Name"][field_idx],
-                name
-            )
-        };
-
-        // Remove from backing store
-        let conn = rusqlite::Connection::open(&self.path)?;
-        conn.execute(&query, rusqlite::params![])?;
-        conn.close().unwrap();
-
-        if name.is_empty() {
-            // Retain all except cookies from the specified domain
-            self.cookies.retain(|c| c.host != domain)
-        } else {
-            // Retain all cookies except those that match `name`
-            // and are present on the specified domain
-            self.cookies.retain(|c| {
-                if c.host == domain {
-                    c.name != name
-                } else {
-                    true
-                }
-            })
-        }
+        cookies: &[Cookie],
+    ) -> Result<(), CookieError> {
+        let cookies: Vec<Cookie> = cookies
+            .iter()
+            .filter(|c| !c.from_session_store)
+            .cloned()
+            .collect();
+        self.store()?.delete(&cookies)?;
 
+        self.cookies
+            .retain(|c| !cookies.iter().any(|d| d.is_same_cookie(c)));
+        self.index_hosts();
         Ok(())
     }
 
-    /// Deduplicated list of domains stored in the database
-    /// Returns a new `String` to dodge BC
+    /// Sorted list of the domains stored in the database
     pub fn domains(&self) -> Vec<String> {
-        let mut hst_names: Vec<String> =
-            self.cookies.iter().map(|c| c.host.to_owned()).collect();
-        hst_names.sort();
-        hst_names.dedup();
-        hst_names
+        self.host_index.keys().cloned().collect()
     }
 
-    /// List of cookies for a specific domain
-    /// To return a non-reference list of cookies requires that
-    /// `Cookie` implements the copy trait
-    pub fn cookies_for_domain(&self, domain: &String) -> Vec<Cookie> {
-        self.cookies
-            .iter()
-            .filter(|c| c.host == *domain)
-            .map(|c| c.to_owned())
-            .collect()
-    }
-
-    /// Return a cookie with a specific name from a specific domain
-    pub fn cookie_for_domain(
-        &self,
-        name: &String,
-        domain: &String,
-    ) -> Option<&Cookie> {
-        self.cookies
-            .iter()
-            .find(|c| c.host == *domain && c.name == *name)
+    /// List of cookies for a specific domain, in the order
+    /// they were read from the database
+    pub fn cookies_for_domain(&self, domain: &str) -> Vec<&Cookie> {
+        self.host_index
+            .get(domain)
+            .map(|idxs| idxs.iter().map(|i| &self.cookies[*i]).collect())
+            .unwrap_or_default()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::cookie::Cookie;
     use crate::cookie_db::CookieDB;
     use crate::path::PathBuf;
     use crate::util::{get_home, DbType};
 
     #[test]
     fn test_path_short() {
-        let mut cdb = CookieDB {
-            path: PathBuf::from("./cookies.sqlite"),
-            typing: DbType::Chrome,
-            cookies: vec![],
-        };
+        let mut cdb = CookieDB::new(
+            PathBuf::from("./cookies.sqlite"),
+            DbType::Chrome,
+            "Chromium",
+        );
         assert_eq!(cdb.path_short(), "./cookies.sqlite");
 
         cdb.path = PathBuf::from("../../var/Cookies");
         assert_eq!(cdb.path_short(), "../../var/Cookies");
 
-        cdb.path = PathBuf::from(format!(
-            "{}/.config/chromium/Default/Cookies",
-            get_home()
-        ));
-        assert_eq!(cdb.path_short(), "~/.config/chromium/Default");
+        cdb.path = PathBuf::from("/var/lib/Default/Cookies");
+        assert_eq!(cdb.path_short(), "/var/lib/Default");
+
+        if let Some(home) = get_home() {
+            cdb.path = PathBuf::from(format!(
+                "{}/.config/chromium/Default/Cookies",
+                home
+            ));
+            assert_eq!(cdb.path_short(), "~/.config/chromium/Default");
+        }
+    }
+
+    #[test]
+    fn test_load_optional_fields() {
+        let path = std::env::temp_dir().join("cookiecutter_test_moz.sqlite");
+        let _ = std::fs::remove_file(&path);
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        // An older Firefox schema without `isPartitionedAttributeSet`
+        conn.execute_batch(
+            "CREATE TABLE moz_cookies (id INTEGER PRIMARY KEY,
+                originAttributes TEXT NOT NULL DEFAULT '', name TEXT,
+                value TEXT, host TEXT, path TEXT, expiry INTEGER,
+                lastAccessed INTEGER, creationTime INTEGER,
+                isSecure INTEGER, isHttpOnly INTEGER, sameSite INTEGER,
+                schemeMap INTEGER);
+             INSERT INTO moz_cookies VALUES (1, '^userContextId=2', 'sid',
+                'x', '.example.com', '/', 1700000000, 0, 0, 1, 1, 1, 2);",
+        )
+        .unwrap();
+        conn.close().unwrap();
+
+        let mut cdb = CookieDB::new(path.clone(), DbType::Firefox, "Firefox");
+        cdb.load_cookies().unwrap();
+        let cookie = cdb.cookies.first().unwrap();
+        assert_eq!(
+            cookie.origin_attributes.as_deref(),
+            Some("^userContextId=2")
+        );
+        assert_eq!(cookie.match_field("SchemeMap", true), "SchemeMap: https");
+        assert_eq!(cookie.is_partitioned_attribute_set, None);
+        assert_eq!(cookie.match_field("Priority", true), "");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_host_index() {
+        let path = std::env::temp_dir().join("cookiecutter_test_index.sqlite");
+        let _ = std::fs::remove_file(&path);
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE moz_cookies (id INTEGER PRIMARY KEY,
+                originAttributes TEXT NOT NULL DEFAULT '', name TEXT,
+                value TEXT, host TEXT, path TEXT, expiry INTEGER,
+                lastAccessed INTEGER, creationTime INTEGER,
+                isSecure INTEGER, isHttpOnly INTEGER, sameSite INTEGER);
+             INSERT INTO moz_cookies VALUES
+                (1, '', 'b', 'x', 'site.org', '/', 0, 0, 0, 0, 0, 0),
+                (2, '', 'sid', 'x', '.example.com', '/', 0, 0, 0, 0, 0, 0),
+                (3, '', 'a', 'x', 'site.org', '/', 0, 0, 0, 0, 0, 0);",
+        )
+        .unwrap();
+        conn.close().unwrap();
+
+        let mut cdb = CookieDB::new(path.clone(), DbType::Firefox, "Firefox");
+        assert!(!cdb.loaded);
+        cdb.load_cookies().unwrap();
+        assert!(cdb.loaded);
+        assert_eq!(cdb.domains(), [".example.com", "site.org"]);
+        let names: Vec<&str> = cdb
+            .cookies_for_domain("site.org")
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["b", "a"]);
+
+        let example: Vec<Cookie> = cdb
+            .cookies_for_domain(".example.com")
+            .into_iter()
+            .cloned()
+            .collect();
+        cdb.delete_cookies(&example).unwrap();
+        assert_eq!(cdb.domains(), ["site.org"]);
+        assert_eq!(cdb.cookies_for_domain("site.org").len(), 2);
+        assert!(cdb.cookies_for_domain(".example.com").is_empty());
+
+        std::fs::remove_file(&path).unwrap();
     }
 }
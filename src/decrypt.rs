@@ -0,0 +1,279 @@
+use std::path::Path;
+
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use sha1::Sha1;
+
+use crate::config::{
+    CHROMIUM_CBC_IV, CHROMIUM_GCM_NONCE_LEN, CHROMIUM_HOST_DIGEST_LEN,
+    CHROMIUM_HOST_DIGEST_VERSION, CHROMIUM_LINUX_ITERATIONS,
+    CHROMIUM_LINUX_V10_PASSWORD, CHROMIUM_MACOS_ITERATIONS,
+    CHROMIUM_PBKDF2_SALT, CHROMIUM_V10_PREFIX, CHROMIUM_V11_PREFIX,
+};
+use crate::key_provider::KeyProvider;
+use crate::util::debug_log;
+
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+/// The operating system that a Chromium profile was created on,
+/// the encryption scheme of cookie values differs between them
+#[derive(Debug, PartialEq)]
+pub enum Platform {
+    Linux,
+    MacOs,
+    Windows,
+}
+
+impl Platform {
+    /// Guess the platform from the location of the cookie database,
+    /// profiles copied from other systems keep their home directory layout
+    pub fn from_path(path: &Path) -> Self {
+        let path = path.to_string_lossy();
+        if path.contains("AppData/Local") || path.contains("AppData/Roaming") {
+            Platform::Windows
+        } else if path.contains("Library/Application Support") {
+            Platform::MacOs
+        } else {
+            Platform::Linux
+        }
+    }
+}
+
+/// Parse a hex encoded 256-bit key, whitespace is ignored
+pub fn parse_key_hex(hex: &str) -> Result<[u8; 32], String> {
+    let hex: String = hex.chars().filter(|c| !c.is_whitespace()).collect();
+    if hex.len() != 64 {
+        return Err(format!(
+            "Expected 64 hex characters for a 256-bit key, got {}",
+            hex.len()
+        ));
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+            .map_err(|_| "Invalid hex character in key".to_string())?;
+    }
+    Ok(key)
+}
+
+/// Decrypt a `v10` value from a Windows Chromium profile.
+///
+/// The master key is stored DPAPI-wrapped in `Local State` and needs to be
+/// unwrapped beforehand. Values are encrypted with AES-256-GCM as:
+///     "v10" || nonce (12 bytes) || ciphertext || tag (16 bytes)
+///
+/// Values encrypted directly with DPAPI (pre Chromium 80) and app-bound
+/// `v20` values can not be decrypted offline and return `None`.
+pub fn decrypt_windows(key: &[u8; 32], encrypted: &[u8]) -> Option<Vec<u8>> {
+    let payload = encrypted.strip_prefix(CHROMIUM_V10_PREFIX)?;
+    if payload.len() < CHROMIUM_GCM_NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = payload.split_at(CHROMIUM_GCM_NONCE_LEN);
+    let cipher = Aes256Gcm::new(key.into());
+    cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()
+}
+
+/// Derive the AES-128 key that macOS and Linux values are encrypted with
+/// from a password, macOS uses 1003 iterations and Linux a single one
+pub fn derive_cbc_key(password: &str, iterations: u32) -> [u8; 16] {
+    pbkdf2::pbkdf2_hmac_array::<Sha1, 16>(
+        password.as_bytes(),
+        CHROMIUM_PBKDF2_SALT,
+        iterations,
+    )
+}
+
+/// Decrypt a value from a macOS or Linux Chromium profile with the version
+/// prefix removed. Values are encrypted with AES-128-CBC and a constant IV
+/// of 16 spaces as:
+///     "v10" | "v11" || ciphertext (PKCS#7 padded)
+pub fn decrypt_cbc(key: &[u8; 16], ciphertext: &[u8]) -> Option<Vec<u8>> {
+    Aes128CbcDec::new(key.into(), &CHROMIUM_CBC_IV.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .ok()
+}
+
+/// Decrypts the values of a single database. The password is only
+/// requested from the key provider once a value needs it, since looking
+/// it up in a keyring can be slow.
+pub struct ValueDecryptor<'a> {
+    platform: Platform,
+    browser: &'a str,
+    windows_key: Option<[u8; 32]>,
+    provider: Option<&'a dyn KeyProvider>,
+    /// Key derived from the provider's password, set after the first lookup
+    password_key: Option<Option<[u8; 16]>>,
+}
+
+impl<'a> ValueDecryptor<'a> {
+    pub fn new(
+        platform: Platform,
+        browser: &'a str,
+        windows_key: Option<[u8; 32]>,
+        provider: Option<&'a dyn KeyProvider>,
+    ) -> Self {
+        ValueDecryptor {
+            platform,
+            browser,
+            windows_key,
+            provider,
+            password_key: None,
+        }
+    }
+
+    /// Decrypt an `encrypted_value`, `None` if no key is available for it
+    pub fn decrypt(&mut self, encrypted: &[u8]) -> Option<Vec<u8>> {
+        match self.platform {
+            Platform::Windows => {
+                decrypt_windows(self.windows_key.as_ref()?, encrypted)
+            }
+            Platform::MacOs => {
+                let ciphertext = encrypted.strip_prefix(CHROMIUM_V10_PREFIX)?;
+                let key = self.password_key(CHROMIUM_MACOS_ITERATIONS)?;
+                decrypt_cbc(&key, ciphertext)
+            }
+            Platform::Linux => {
+                // `v10` values are encrypted with a hardcoded password when
+                // no keyring is available, `v11` values use the keyring
+                if let Some(ciphertext) =
+                    encrypted.strip_prefix(CHROMIUM_V10_PREFIX)
+                {
+                    let key = derive_cbc_key(
+                        CHROMIUM_LINUX_V10_PASSWORD,
+                        CHROMIUM_LINUX_ITERATIONS,
+                    );
+                    decrypt_cbc(&key, ciphertext)
+                } else {
+                    let ciphertext =
+                        encrypted.strip_prefix(CHROMIUM_V11_PREFIX)?;
+                    let key = self.password_key(CHROMIUM_LINUX_ITERATIONS)?;
+                    decrypt_cbc(&key, ciphertext)
+                }
+            }
+        }
+    }
+
+    fn password_key(&mut self, iterations: u32) -> Option<[u8; 16]> {
+        if self.password_key.is_none() {
+            let password = self.provider?.password(self.browser);
+            self.password_key = Some(match password {
+                Ok(password) => Some(derive_cbc_key(&password, iterations)),
+                // Printing would corrupt the TUI if it has the terminal,
+                // the values are left encrypted instead
+                Err(err) => {
+                    debug_log(format!("{}: {err}", self.browser));
+                    None
+                }
+            });
+        }
+        self.password_key.flatten()
+    }
+}
+
+/// Convert a decrypted value into a string. Databases from schema version
+/// 24 onwards prefix the plaintext with the SHA-256 digest of the host.
+pub fn plaintext_value(plaintext: &[u8], db_version: i64) -> String {
+    let value = if db_version >= CHROMIUM_HOST_DIGEST_VERSION
+        && plaintext.len() >= CHROMIUM_HOST_DIGEST_LEN
+    {
+        &plaintext[CHROMIUM_HOST_DIGEST_LEN..]
+    } else {
+        plaintext
+    };
+    String::from_utf8_lossy(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use aes::cipher::{block_padding::Pkcs7, BlockEncryptMut, KeyIvInit};
+    use aes_gcm::aead::Aead;
+    use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+
+    use crate::decrypt::{
+        decrypt_windows, derive_cbc_key, parse_key_hex, plaintext_value,
+        Platform, ValueDecryptor,
+    };
+    use crate::key_provider::{KeyProvider, StaticPassword};
+
+    /// Encrypt a value like Chromium on macOS and Linux
+    fn encrypt_cbc(password: &str, iterations: u32, prefix: &[u8]) -> Vec<u8> {
+        let key = derive_cbc_key(password, iterations);
+        let plaintext = [[0u8; 32].as_ref(), b"secret"].concat();
+        let ciphertext =
+            cbc::Encryptor::<aes::Aes128>::new(&key.into(), &[b' '; 16].into())
+                .encrypt_padded_vec_mut::<Pkcs7>(&plaintext);
+        [prefix, &ciphertext].concat()
+    }
+
+    #[test]
+    fn test_decrypt_windows() {
+        let key = parse_key_hex(&"0f".repeat(32)).unwrap();
+        let nonce = [7u8; 12];
+        let ciphertext = Aes256Gcm::new(&key.into())
+            .encrypt(Nonce::from_slice(&nonce), b"secret".as_ref())
+            .unwrap();
+        let encrypted = [b"v10".as_ref(), &nonce, &ciphertext].concat();
+
+        let plaintext = decrypt_windows(&key, &encrypted).unwrap();
+        assert_eq!(plaintext_value(&plaintext, 23), "secret");
+
+        let wrong_key = [0u8; 32];
+        assert_eq!(decrypt_windows(&wrong_key, &encrypted), None);
+        assert_eq!(decrypt_windows(&key, b"\x01\x00\x00\x00"), None);
+    }
+
+    #[test]
+    fn test_decrypt_cbc() {
+        let provider = StaticPassword("s3cr3t".to_string());
+        let decrypt = |platform, encrypted: &[u8]| {
+            ValueDecryptor::new(
+                platform,
+                "Chrome",
+                None,
+                Some(&provider as &dyn KeyProvider),
+            )
+            .decrypt(encrypted)
+            .map(|p| plaintext_value(&p, 24))
+        };
+        let secret = Some("secret".to_string());
+
+        let macos = encrypt_cbc("s3cr3t", 1003, b"v10");
+        assert_eq!(decrypt(Platform::MacOs, &macos), secret);
+
+        let linux_v10 = encrypt_cbc("peanuts", 1, b"v10");
+        let linux_v11 = encrypt_cbc("s3cr3t", 1, b"v11");
+        assert_eq!(decrypt(Platform::Linux, &linux_v10), secret);
+        assert_eq!(decrypt(Platform::Linux, &linux_v11), secret);
+
+        let mut without_provider =
+            ValueDecryptor::new(Platform::Linux, "Chrome", None, None);
+        assert_eq!(without_provider.decrypt(&linux_v11), None);
+    }
+
+    #[test]
+    fn test_platform_from_path() {
+        assert_eq!(
+            Platform::from_path(Path::new(
+                "/mnt/c/Users/me/AppData/Local/Google/Chrome/User Data/Default/Network/Cookies"
+            )),
+            Platform::Windows
+        );
+        assert_eq!(
+            Platform::from_path(Path::new(
+                "/home/me/.config/chromium/Default/Cookies"
+            )),
+            Platform::Linux
+        );
+        assert_eq!(
+            Platform::from_path(Path::new(
+                "/mnt/backup/Users/me/Library/Application Support/Google/Chrome/Default/Cookies"
+            )),
+            Platform::MacOs
+        );
+        assert!(parse_key_hex("abc").is_err());
+    }
+}
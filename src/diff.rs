@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+
+use crate::cookie::Cookie;
+
+/// Fields that are compared between two versions of a cookie, the access
+/// and update times change whenever a cookie is sent and are ignored
+pub const COMPARED_FIELDS: &[&str] = &[
+    "Value", "Creation", "Expiry", "HttpOnly", "Secure", "SameSite",
+];
+
+/// The identity of a cookie across two loads of a database: the host,
+/// name, path and `originAttributes`. The partition key takes the place
+/// of the origin attributes for Chromium cookies.
+pub type CookieKey = (String, String, String, String);
+
+pub fn cookie_key(cookie: &Cookie) -> CookieKey {
+    (
+        cookie.host.to_owned(),
+        cookie.name.to_owned(),
+        cookie.path.to_owned(),
+        cookie
+            .origin_attributes
+            .clone()
+            .or(cookie.top_frame_site_key.clone())
+            .unwrap_or_default(),
+    )
+}
+
+#[derive(Debug, Clone)]
+pub enum CookieChange {
+    Added(Cookie),
+    Removed(Cookie),
+    Changed { old: Box<Cookie>, new: Box<Cookie> },
+}
+
+impl CookieChange {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CookieChange::Added(_) => "added",
+            CookieChange::Removed(_) => "removed",
+            CookieChange::Changed { .. } => "changed",
+        }
+    }
+
+    pub fn sign(&self) -> &'static str {
+        match self {
+            CookieChange::Added(_) => "+",
+            CookieChange::Removed(_) => "-",
+            CookieChange::Changed { .. } => "~",
+        }
+    }
+
+    /// Everything that is shown for a change after the name of the
+    /// cookie: a non-default path, the container and partition key,
+    /// followed by the value of an added cookie or the changed fields
+    pub fn details(&self) -> String {
+        let c = self.cookie();
+        let mut details = String::new();
+        if c.path != "/" {
+            details += &format!(" {}", c.path);
+        }
+        if let Some(container) = &c.container {
+            details += &format!(" [{container}]");
+        }
+        if let Some(partition_key) = c.partition_key() {
+            details += &format!(" ({partition_key})");
+        }
+        match self {
+            CookieChange::Added(_) => {
+                details += &format!(" = {}", c.match_field("Value", false));
+            }
+            CookieChange::Removed(_) => {}
+            CookieChange::Changed { .. } => {
+                for (field, old, new) in self.changed_fields() {
+                    details += &format!(" {field}: {old} -> {new}");
+                }
+            }
+        }
+        details
+    }
+
+    /// The current version of the cookie, or the removed cookie
+    pub fn cookie(&self) -> &Cookie {
+        match self {
+            CookieChange::Added(c) | CookieChange::Removed(c) => c,
+            CookieChange::Changed { new, .. } => new,
+        }
+    }
+
+    /// The old and new value of each field that differs between the two
+    /// versions of a changed cookie, formatted as for `cookies --fields`
+    pub fn changed_fields(&self) -> Vec<(&'static str, String, String)> {
+        let CookieChange::Changed { old, new } = self else {
+            return vec![];
+        };
+        COMPARED_FIELDS
+            .iter()
+            .map(|f| (*f, old.match_field(f, false), new.match_field(f, false)))
+            .filter(|(_, old, new)| old != new)
+            .collect()
+    }
+}
+
+/// Whether any of the compared fields differ, encrypted values
+/// are compared as is if they could not be decrypted
+fn has_changed(old: &Cookie, new: &Cookie) -> bool {
+    old.value != new.value
+        || old.encrypted_value != new.encrypted_value
+        || old.creation != new.creation
+        || old.expiry != new.expiry
+        || old.http_only != new.http_only
+        || old.secure != new.secure
+        || old.samesite != new.samesite
+}
+
+/// The changes from one set of cookies to another, ordered by
+/// the key of each cookie
+pub fn diff_cookies(old: &[Cookie], new: &[Cookie]) -> Vec<CookieChange> {
+    let old: BTreeMap<CookieKey, &Cookie> =
+        old.iter().map(|c| (cookie_key(c), c)).collect();
+    let new: BTreeMap<CookieKey, &Cookie> =
+        new.iter().map(|c| (cookie_key(c), c)).collect();
+
+    let mut changes: Vec<(&CookieKey, CookieChange)> = vec![];
+    for (key, old_cookie) in old.iter() {
+        match new.get(key) {
+            Some(new_cookie) if has_changed(old_cookie, new_cookie) => {
+                changes.push((
+                    key,
+                    CookieChange::Changed {
+                        old: Box::new((*old_cookie).clone()),
+                        new: Box::new((*new_cookie).clone()),
+                    },
+                ));
+            }
+            Some(_) => {}
+            None => changes
+                .push((key, CookieChange::Removed((*old_cookie).clone()))),
+        }
+    }
+    for (key, new_cookie) in new.iter() {
+        if !old.contains_key(key) {
+            changes.push((key, CookieChange::Added((*new_cookie).clone())));
+        }
+    }
+    changes.sort_by(|a, b| a.0.cmp(b.0));
+    changes.into_iter().map(|(_, change)| change).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cookie::tests::cookie;
+    use crate::diff::diff_cookies;
+
+    #[test]
+    fn test_diff_cookies() {
+        let mut container = cookie("example.com", "sid", "1");
+        container.origin_attributes = Some("^userContextId=2".to_string());
+        let mut accessed = cookie("site.org", "pref", "x");
+        let old = vec![
+            cookie("example.com", "sid", "1"),
+            cookie("example.com", "gone", "1"),
+            accessed.clone(),
+        ];
+        accessed.last_access = 1_700_000_000;
+        let new = vec![
+            container,
+            cookie("example.com", "sid", "2"),
+            accessed,
+            cookie("new.net", "id", "1"),
+        ];
+
+        let changes = diff_cookies(&old, &new);
+        let kinds: Vec<(&str, &str)> = changes
+            .iter()
+            .map(|c| (c.kind(), c.cookie().name.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("removed", "gone"),
+                ("changed", "sid"),
+                ("added", "sid"),
+                ("added", "id")
+            ]
+        );
+        assert_eq!(
+            changes[1].changed_fields(),
+            [("Value", "1".to_string(), "2".to_string())]
+        );
+        assert_eq!(
+            changes[2].cookie().origin_attributes.as_deref(),
+            Some("^userContextId=2")
+        );
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::browser::Browser;
+use crate::config::{Config, DISCOVERY_CACHE, SKIPPED_DIRS};
+use crate::cookie_db::CookieDB;
+use crate::util::{cookie_db_type, home_dirs, DbType};
+
+/// The detected type of a database and the modification time of the
+/// file when it was detected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    db_type: DbType,
+    mtime: SystemTime,
+}
+
+/// The detected type of each cookie database from earlier runs, this
+/// saves opening every database twice when profiles are discovered
+#[derive(Debug, Default)]
+struct DiscoveryCache {
+    path: Option<PathBuf>,
+    entries: HashMap<PathBuf, CacheEntry>,
+    modified: bool,
+}
+
+impl DiscoveryCache {
+    /// The cache location, `$XDG_CACHE_HOME/cookiecutter/discovery.json`
+    fn default_path() -> Option<PathBuf> {
+        let cache_home = match std::env::var("XDG_CACHE_HOME") {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var("HOME").ok()?).join(".cache"),
+        };
+        Some(cache_home.join(DISCOVERY_CACHE))
+    }
+
+    /// Read the cache, a missing or invalid cache is treated as empty.
+    /// Databases that no longer exist are dropped.
+    fn load(path: Option<PathBuf>) -> Self {
+        let mut entries: HashMap<PathBuf, CacheEntry> = path
+            .as_ref()
+            .and_then(|p| std::fs::read(p).ok())
+            .and_then(|content| serde_json::from_slice(&content).ok())
+            .unwrap_or_default();
+        let count = entries.len();
+        entries.retain(|p, _| p.is_file());
+        DiscoveryCache {
+            modified: entries.len() != count,
+            path,
+            entries,
+        }
+    }
+
+    /// The cached type of a database, unless the file was modified
+    /// (e.g. replaced by a different database) since it was detected
+    fn db_type(&self, path: &Path, mtime: SystemTime) -> Option<DbType> {
+        self.entries
+            .get(path)
+            .filter(|entry| entry.mtime == mtime)
+            .map(|entry| entry.db_type.clone())
+    }
+
+    fn insert(&mut self, path: PathBuf, db_type: DbType, mtime: SystemTime) {
+        self.entries.insert(path, CacheEntry { db_type, mtime });
+        self.modified = true;
+    }
+
+    /// Write the cache if any entries were added or removed
+    fn save(&self) {
+        let Some(path) = self.path.as_ref().filter(|_| self.modified) else {
+            return;
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| {
+                let json = serde_json::to_vec(&self.entries)?;
+                std::fs::write(path, json)
+            });
+        if let Err(err) = result {
+            if Config::global().debug {
+                eprintln!("Failed to write {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+/// Candidate database files of a browser below `search_path`. The walk
+/// is bounded by the depth of the browser's profile layout and skips
+/// cache and storage directories, which can contain many thousands of
+/// files in a profile.
+fn db_candidates(browser: &Browser, search_path: &Path) -> Vec<PathBuf> {
+    let db_names = browser.db_names();
+    WalkDir::new(search_path)
+        .follow_links(false)
+        .max_depth(browser.walk_depth())
+        .into_iter()
+        .filter_entry(|e| {
+            !(e.file_type().is_dir()
+                && SKIPPED_DIRS
+                    .contains(&e.file_name().to_string_lossy().as_ref()))
+        })
+        // The filter is used to skip inaccessible paths
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.file_type().is_file()
+                && db_names.iter().any(|n| e.file_name() == n.as_str())
+        })
+        .map(|e| e.into_path())
+        .collect()
+}
+
+/// The directories below `home` that match a search directory,
+/// which can be a glob pattern
+fn search_paths(home: &Path, search_dir: &str) -> Vec<PathBuf> {
+    let pattern = format!(
+        "{}/{search_dir}",
+        glob::Pattern::escape(&home.to_string_lossy())
+    );
+    let Ok(paths) = glob::glob(&pattern) else {
+        return vec![];
+    };
+    paths
+        .filter_map(|p| p.ok())
+        .filter(|p| p.is_dir())
+        .collect()
+}
+
+/// Search the directories of all configured browsers in each of the
+/// `home_dirs()` for cookie databases and add each path to the provided
+/// set. Directories are walked and databases are opened in parallel.
+pub fn cookie_dbs_from_profiles(cookie_dbs: &mut HashSet<CookieDB>) {
+    // Databases below a --root (e.g. a mounted backup) are not cached,
+    // they would otherwise stay in the cache of the user
+    let cache_path = match Config::global().root {
+        Some(_) => None,
+        None => DiscoveryCache::default_path(),
+    };
+    let mut cache = DiscoveryCache::load(cache_path);
+
+    let search_paths: Vec<(&Browser, PathBuf)> = home_dirs()
+        .iter()
+        .flat_map(|home| {
+            Config::global().browsers.iter().flat_map(move |browser| {
+                browser.search_dirs.iter().flat_map(move |dir| {
+                    search_paths(home, dir)
+                        .into_iter()
+                        .map(move |path| (browser, path))
+                })
+            })
+        })
+        .collect();
+
+    let candidates: Vec<(&Browser, PathBuf)> = search_paths
+        .par_iter()
+        .flat_map_iter(|(browser, search_path)| {
+            db_candidates(browser, search_path)
+                .into_iter()
+                .map(move |path| (*browser, path))
+        })
+        .collect();
+
+    // Each database with the modification time to cache its type with,
+    // if it was not cached yet
+    let found: Vec<(CookieDB, Option<SystemTime>)> = candidates
+        .into_par_iter()
+        .filter_map(|(browser, path)| {
+            let mtime =
+                std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            let cached = mtime.and_then(|mtime| cache.db_type(&path, mtime));
+            let (db_type, uncached_mtime) = match cached {
+                Some(db_type) => (db_type, None),
+                None => {
+                    (cookie_db_type(&path).unwrap_or(DbType::Unknown), mtime)
+                }
+            };
+            if db_type == DbType::Unknown {
+                return None;
+            }
+            let mut cookie_db = CookieDB::new(path, db_type, &browser.name);
+            cookie_db.load_profile_info();
+            Some((cookie_db, uncached_mtime))
+        })
+        .collect();
+
+    for (cookie_db, uncached_mtime) in found {
+        if let Some(mtime) = uncached_mtime {
+            cache.insert(
+                cookie_db.path.to_owned(),
+                cookie_db.typing.clone(),
+                mtime,
+            );
+        }
+        cookie_dbs.insert(cookie_db);
+    }
+    cache.save();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use crate::browser::Browser;
+    use crate::discovery::{db_candidates, search_paths, DiscoveryCache};
+    use crate::util::DbType;
+
+    #[test]
+    fn test_discovery_cache() {
+        let dir = std::env::temp_dir().join("cookiecutter_test_cache");
+        std::fs::create_dir_all(&dir).unwrap();
+        let db = dir.join("Cookies");
+        std::fs::write(&db, b"").unwrap();
+        let mtime = std::fs::metadata(&db).unwrap().modified().unwrap();
+
+        let cache_path = dir.join("discovery.json");
+        let mut cache = DiscoveryCache::load(Some(cache_path.clone()));
+        cache.insert(db.clone(), DbType::Chrome, mtime);
+        cache.save();
+
+        let cache = DiscoveryCache::load(Some(cache_path.clone()));
+        assert_eq!(cache.db_type(&db, mtime), Some(DbType::Chrome));
+        // The file was replaced after its type was cached
+        let later = mtime + Duration::from_secs(60);
+        assert_eq!(cache.db_type(&db, later), None);
+
+        std::fs::remove_file(&db).unwrap();
+        assert!(DiscoveryCache::load(Some(cache_path)).entries.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_search_paths() {
+        let home = std::env::temp_dir().join("cookiecutter_test_sandboxes");
+        let dirs = [
+            ".var/app/org.mozilla.firefox/.mozilla/firefox",
+            // An app id that is not known to cookiecutter
+            ".var/app/org.example.Chromium/config/chromium",
+            "snap/chromium/common/chromium",
+            "snap/brave/current/.config/BraveSoftware/Brave-Browser",
+        ];
+        for dir in dirs {
+            std::fs::create_dir_all(home.join(dir)).unwrap();
+        }
+
+        let mut found: Vec<(String, PathBuf)> = Browser::builtin()
+            .into_iter()
+            .flat_map(|b| {
+                b.search_dirs
+                    .iter()
+                    .flat_map(|dir| search_paths(&home, dir))
+                    .map(|path| (b.name.to_owned(), path))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                ("Brave".to_string(), home.join(dirs[3])),
+                ("Chromium".to_string(), home.join(dirs[1])),
+                ("Chromium".to_string(), home.join(dirs[2])),
+                ("Firefox".to_string(), home.join(dirs[0])),
+            ]
+        );
+        std::fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn test_db_candidates() {
+        let root = std::env::temp_dir().join("cookiecutter_test_discovery");
+        for file in [
+            "Default/Network/Cookies",
+            "Default/Service Worker/CacheStorage/Cookies",
+            "Profile 1/Cookies",
+            "Profile 1/Extensions/abc/1.0/Cookies",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        }
+        let browser = Browser {
+            name: "Chromium".to_string(),
+            typing: DbType::Chrome,
+            search_dirs: vec![],
+            db_names: vec![],
+            max_depth: None,
+        };
+
+        let mut candidates = db_candidates(&browser, &root);
+        candidates.sort();
+        assert_eq!(
+            candidates,
+            vec![
+                root.join("Default/Network/Cookies"),
+                root.join("Profile 1/Cookies")
+            ]
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,39 @@
+use std::{fmt, io};
+
+/// Errors from reading or modifying a cookie database
+#[derive(Debug)]
+pub enum CookieError {
+    Sqlite(rusqlite::Error),
+    Io(io::Error),
+    /// The database does not follow the expected file format
+    Format(String),
+    /// The database format can be read but not modified
+    ReadOnly(&'static str),
+}
+
+impl fmt::Display for CookieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CookieError::Sqlite(err) => write!(f, "{err}"),
+            CookieError::Io(err) => write!(f, "{err}"),
+            CookieError::Format(msg) => write!(f, "Invalid format: {msg}"),
+            CookieError::ReadOnly(name) => {
+                write!(f, "{name} cookies can not be modified")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CookieError {}
+
+impl From<rusqlite::Error> for CookieError {
+    fn from(err: rusqlite::Error) -> Self {
+        CookieError::Sqlite(err)
+    }
+}
+
+impl From<io::Error> for CookieError {
+    fn from(err: io::Error) -> Self {
+        CookieError::Io(err)
+    }
+}
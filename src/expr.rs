@@ -0,0 +1,572 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use regex::Regex;
+
+use crate::config::COOKIE_FIELDS;
+use crate::cookie::Cookie;
+
+/// How the values of a field are compared
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    /// UNIX epoch time, compared with dates and durations relative to `now`
+    Time,
+    Bool,
+    Int,
+    /// Integers with names, e.g. `SameSite` can be compared with 1 or `Lax`
+    Enum,
+}
+
+fn field_kind(field: &str) -> Kind {
+    match field {
+        "Creation" | "Expiry" | "LastAccess" | "LastUpdate" => Kind::Time,
+        "HttpOnly"
+        | "Secure"
+        | "PartitionedAttributeSet"
+        | "IsPersistent"
+        | "HasExpires"
+        | "SessionStore" => Kind::Bool,
+        "SourcePort" => Kind::Int,
+        "SameSite" | "SchemeMap" | "Priority" | "SourceScheme" => Kind::Enum,
+        _ => Kind::Text,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Match,
+    NotMatch,
+}
+
+impl Op {
+    fn is_ordering(&self) -> bool {
+        matches!(self, Op::Lt | Op::Le | Op::Gt | Op::Ge)
+    }
+
+    fn accepts(&self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering.is_eq(),
+            Op::Ne => ordering.is_ne(),
+            Op::Lt => ordering.is_lt(),
+            Op::Le => ordering.is_le(),
+            Op::Gt => ordering.is_gt(),
+            Op::Ge => ordering.is_ge(),
+            Op::Match | Op::NotMatch => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Literal {
+    Text(String),
+    Int(i64),
+    Bool(bool),
+    Regex(Regex),
+}
+
+/// The value of a field of a cookie
+enum Value {
+    Text(String),
+    Int(i64),
+    Bool(bool),
+    /// The number and its name
+    Enum(i64, String),
+}
+
+#[derive(Debug)]
+enum Node {
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Compare {
+        field: &'static str,
+        op: Op,
+        literal: Literal,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    Op(Op),
+    /// A quoted string
+    Str(String),
+    /// Field names, keywords, numbers, dates and unquoted strings
+    Word(String),
+}
+
+impl Token {
+    fn is_word(&self, word: &str) -> bool {
+        matches!(self, Token::Word(w) if w.eq_ignore_ascii_case(word))
+    }
+}
+
+/// A parsed `--filter` expression, e.g.
+///     host ~ "google" and expiry < now+7d and not secure
+///
+/// Fields are the same as for `cookies --fields` (case-insensitive) and
+/// can be combined with `and`, `or`, `not` and parentheses. Boolean fields
+/// can be used on their own. Comparisons with a field that is not set for
+/// a cookie are false.
+#[derive(Debug)]
+pub struct FilterExpr {
+    root: Node,
+}
+
+impl FilterExpr {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let tokens =
+            tokenize(expr).map_err(|(pos, msg)| error(expr, pos, &msg))?;
+        let mut parser = Parser {
+            tokens,
+            next: 0,
+            end: expr.chars().count(),
+            now: Utc::now().timestamp(),
+        };
+        let root = parser
+            .parse_or()
+            .and_then(|root| match parser.peek() {
+                None => Ok(root),
+                Some(_) => {
+                    Err((parser.pos(), "Expected 'and' or 'or'".to_string()))
+                }
+            })
+            .map_err(|(pos, msg)| error(expr, pos, &msg))?;
+        Ok(FilterExpr { root })
+    }
+
+    pub fn matches(&self, cookie: &Cookie) -> bool {
+        eval(&self.root, cookie)
+    }
+
+    /// Whether the expression compares the given field, e.g. `Value`
+    pub fn uses_field(&self, field: &str) -> bool {
+        uses_field(&self.root, field)
+    }
+}
+
+/// A parse error with the position in the expression marked
+fn error(expr: &str, pos: usize, message: &str) -> String {
+    format!(
+        "Invalid filter at position {}: {message}\n  {expr}\n  {}^",
+        pos + 1,
+        " ".repeat(pos)
+    )
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || "_-+:.".contains(c)
+}
+
+/// Split an expression into tokens and the position of their first character
+fn tokenize(expr: &str) -> Result<Vec<(Token, usize)>, (usize, String)> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let next = chars.get(i + 1).copied();
+        let token = match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '=' if next == Some('=') => {
+                i += 1;
+                Token::Op(Op::Eq)
+            }
+            '=' => Token::Op(Op::Eq),
+            '!' if next == Some('=') => {
+                i += 1;
+                Token::Op(Op::Ne)
+            }
+            '!' if next == Some('~') => {
+                i += 1;
+                Token::Op(Op::NotMatch)
+            }
+            '<' if next == Some('=') => {
+                i += 1;
+                Token::Op(Op::Le)
+            }
+            '<' => Token::Op(Op::Lt),
+            '>' if next == Some('=') => {
+                i += 1;
+                Token::Op(Op::Ge)
+            }
+            '>' => Token::Op(Op::Gt),
+            '~' => Token::Op(Op::Match),
+            quote @ ('"' | '\'') => {
+                let mut s = String::new();
+                loop {
+                    i += 1;
+                    match chars.get(i) {
+                        None => {
+                            return Err((start, "Unterminated string".into()))
+                        }
+                        Some('\\') if i + 1 < chars.len() => {
+                            i += 1;
+                            s.push(chars[i]);
+                        }
+                        Some(c) if *c == quote => break,
+                        Some(c) => s.push(*c),
+                    }
+                }
+                Token::Str(s)
+            }
+            c if is_word_char(c) => {
+                while chars.get(i + 1).is_some_and(|c| is_word_char(*c)) {
+                    i += 1;
+                }
+                Token::Word(chars[start..=i].iter().collect())
+            }
+            c => return Err((start, format!("Unexpected character '{c}'"))),
+        };
+        tokens.push((token, start));
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+/// Parse a date: `now` with an optional offset (e.g. `now-7d`, units are
+/// s, m, h, d, w and y), a day (`2024-05-01`), an RFC 3339 timestamp or
+/// a UNIX timestamp
+fn parse_date(s: &str, now: i64) -> Option<i64> {
+    if let Ok(epoch) = s.parse::<i64>() {
+        return Some(epoch);
+    }
+    if let Some(offset) = s.to_lowercase().strip_prefix("now") {
+        if offset.is_empty() {
+            return Some(now);
+        }
+        let (sign, duration) = match offset.strip_prefix('+') {
+            Some(duration) => (1, duration),
+            None => (-1, offset.strip_prefix('-')?),
+        };
+        let unit = duration.chars().last()?;
+        let count = &duration[..duration.len() - unit.len_utf8()];
+        let unit_secs = match unit {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            'y' => 365 * 24 * 60 * 60,
+            _ => return None,
+        };
+        let offset =
+            count.parse::<i64>().ok()?.checked_mul(sign * unit_secs)?;
+        return now.checked_add(offset);
+    }
+    if let Ok(day) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return Some(day.and_hms_opt(0, 0, 0)?.and_utc().timestamp());
+    }
+    DateTime::parse_from_rfc3339(s).ok().map(|t| t.timestamp())
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    /// Position of the end of the expression
+    end: usize,
+    now: i64,
+}
+
+type ParseResult<T> = Result<T, (usize, String)>;
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(t, _)| t)
+    }
+
+    /// Position of the next token
+    fn pos(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.end, |(_, pos)| *pos)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.next += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> ParseResult<Node> {
+        let mut node = self.parse_and()?;
+        while self.peek().is_some_and(|t| t.is_word("or")) {
+            self.advance();
+            node = Node::Or(Box::new(node), Box::new(self.parse_and()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_and(&mut self) -> ParseResult<Node> {
+        let mut node = self.parse_not()?;
+        while self.peek().is_some_and(|t| t.is_word("and")) {
+            self.advance();
+            node = Node::And(Box::new(node), Box::new(self.parse_not()?));
+        }
+        Ok(node)
+    }
+
+    fn parse_not(&mut self) -> ParseResult<Node> {
+        if self.peek().is_some_and(|t| t.is_word("not")) {
+            self.advance();
+            return Ok(Node::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> ParseResult<Node> {
+        let pos = self.pos();
+        match self.advance() {
+            Some(Token::LParen) => {
+                let node = self.parse_or()?;
+                match self.advance() {
+                    Some(Token::RParen) => Ok(node),
+                    _ => Err((self.pos() - 1, "Expected ')'".into())),
+                }
+            }
+            Some(Token::Word(word)) => self.parse_comparison(&word, pos),
+            Some(_) => Err((pos, "Expected a field name".into())),
+            None => Err((pos, "Expected a field name".into())),
+        }
+    }
+
+    fn parse_comparison(
+        &mut self,
+        word: &str,
+        pos: usize,
+    ) -> ParseResult<Node> {
+        let Some(field) =
+            COOKIE_FIELDS.keys().find(|f| f.eq_ignore_ascii_case(word))
+        else {
+            return Err((
+                pos,
+                format!("Unknown field '{word}', see `cookies --list-fields`"),
+            ));
+        };
+        let kind = field_kind(field);
+
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            // Boolean fields can be used as conditions on their own
+            _ if kind == Kind::Bool => {
+                return Ok(Node::Compare {
+                    field,
+                    op: Op::Eq,
+                    literal: Literal::Bool(true),
+                });
+            }
+            _ => {
+                return Err((
+                    self.pos(),
+                    format!("Expected an operator after {field}"),
+                ))
+            }
+        };
+        self.advance();
+
+        let pos = self.pos();
+        let (value, quoted) = match self.advance() {
+            Some(Token::Str(s)) => (s, true),
+            Some(Token::Word(w)) => (w, false),
+            _ => return Err((pos, "Expected a value".into())),
+        };
+        let literal = self.literal(field, kind, op, value, quoted);
+        literal
+            .map(|literal| Node::Compare { field, op, literal })
+            .map_err(|msg| (pos, msg))
+    }
+
+    /// Convert a value to the type of the field it is compared with
+    fn literal(
+        &self,
+        field: &str,
+        kind: Kind,
+        op: Op,
+        value: String,
+        quoted: bool,
+    ) -> Result<Literal, String> {
+        if matches!(op, Op::Match | Op::NotMatch) {
+            return Regex::new(&value)
+                .map(Literal::Regex)
+                .map_err(|e| format!("Invalid regex: {e}"));
+        }
+        match kind {
+            Kind::Text => Ok(Literal::Text(value)),
+            Kind::Time => parse_date(&value, self.now)
+                .filter(|_| !quoted)
+                .map(Literal::Int)
+                .ok_or(format!(
+                    "Invalid date '{value}', use e.g. now-7d, \
+                     2024-05-01 or a UNIX timestamp"
+                )),
+            Kind::Bool if op.is_ordering() => {
+                Err(format!("{field} can only be compared with = or !="))
+            }
+            Kind::Bool => match value.to_lowercase().as_str() {
+                "true" => Ok(Literal::Bool(true)),
+                "false" => Ok(Literal::Bool(false)),
+                _ => Err(format!("{field} can only be true or false")),
+            },
+            Kind::Int => value
+                .parse()
+                .map(Literal::Int)
+                .map_err(|_| format!("{field} can only be a number")),
+            Kind::Enum => match value.parse() {
+                Ok(n) => Ok(Literal::Int(n)),
+                Err(_) if op.is_ordering() => Err(format!(
+                    "{field} can only be compared with = or != by name"
+                )),
+                Err(_) => Ok(Literal::Text(value)),
+            },
+        }
+    }
+}
+
+/// The value of a field, `None` if the field is not set for the cookie
+fn field_value(c: &Cookie, field: &str) -> Option<Value> {
+    let name = || c.match_field(field, false);
+    match field {
+        "Creation" => Some(Value::Int(c.creation)),
+        "Expiry" => Some(Value::Int(c.expiry)),
+        "LastAccess" => Some(Value::Int(c.last_access)),
+        "LastUpdate" => c.last_update.map(Value::Int),
+        "HttpOnly" => Some(Value::Bool(c.http_only)),
+        "Secure" => Some(Value::Bool(c.secure)),
+        "PartitionedAttributeSet" => {
+            c.is_partitioned_attribute_set.map(Value::Bool)
+        }
+        "IsPersistent" => c.is_persistent.map(Value::Bool),
+        "HasExpires" => c.has_expires.map(Value::Bool),
+        "SessionStore" => Some(Value::Bool(c.from_session_store)),
+        "SourcePort" => c.source_port.map(Value::Int),
+        "SameSite" => Some(Value::Enum(c.samesite as i64, name())),
+        "SchemeMap" => c.scheme_map.map(|n| Value::Enum(n, name())),
+        "Priority" => c.priority.map(|n| Value::Enum(n, name())),
+        "SourceScheme" => c.source_scheme.map(|n| Value::Enum(n, name())),
+        "OriginAttributes" => c.origin_attributes.clone().map(Value::Text),
+        "TopFrameSiteKey" => c.top_frame_site_key.clone().map(Value::Text),
+        "Container" => c.container.clone().map(Value::Text),
+        "PartitionKey" => c.partition_key().map(Value::Text),
+        // Host, Name, Path and the decrypted Value
+        _ => Some(Value::Text(name())),
+    }
+}
+
+fn uses_field(node: &Node, name: &str) -> bool {
+    match node {
+        Node::And(a, b) | Node::Or(a, b) => {
+            uses_field(a, name) || uses_field(b, name)
+        }
+        Node::Not(a) => uses_field(a, name),
+        Node::Compare { field, .. } => *field == name,
+    }
+}
+
+fn eval(node: &Node, c: &Cookie) -> bool {
+    match node {
+        Node::And(a, b) => eval(a, c) && eval(b, c),
+        Node::Or(a, b) => eval(a, c) || eval(b, c),
+        Node::Not(a) => !eval(a, c),
+        Node::Compare { field, op, literal } => {
+            let Some(value) = field_value(c, field) else {
+                return false;
+            };
+            match (value, literal) {
+                // Regexes match the value as listed by `cookies --fields`
+                (_, Literal::Regex(re)) => {
+                    re.is_match(&c.match_field(field, false))
+                        == (*op == Op::Match)
+                }
+                (Value::Text(v), Literal::Text(l)) => op.accepts(v.cmp(l)),
+                (Value::Int(v) | Value::Enum(v, _), Literal::Int(l)) => {
+                    op.accepts(v.cmp(l))
+                }
+                (Value::Enum(_, name), Literal::Text(l)) => {
+                    op.accepts(name.to_lowercase().cmp(&l.to_lowercase()))
+                }
+                (Value::Bool(v), Literal::Bool(l)) => op.accepts(v.cmp(l)),
+                _ => false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::cookie::tests::cookie;
+    use crate::cookie::Cookie;
+    use crate::expr::FilterExpr;
+
+    #[test]
+    fn test_filter_expr() {
+        let now = Utc::now().timestamp();
+        let expiring = |host: &str, secure: bool, expiry: i64| Cookie {
+            secure,
+            expiry,
+            ..cookie(host, "id", "abc")
+        };
+        let soon = expiring(".google.com", false, now + 60 * 60);
+        let later = expiring("www.google.com", true, now + 30 * 24 * 60 * 60);
+        let other = expiring("example.com", false, now + 60);
+
+        let expr = FilterExpr::parse(
+            r#"host ~ "google" and expiry < now+7d and not secure"#,
+        )
+        .unwrap();
+        assert!(expr.matches(&soon));
+        assert!(!expr.matches(&later));
+        assert!(!expr.matches(&other));
+        assert!(!expr.uses_field("Value"));
+        let expr = FilterExpr::parse("secure or not VALUE ~ '^x'").unwrap();
+        assert!(expr.uses_field("Value"));
+
+        let expr = FilterExpr::parse(
+            "(Secure or HOST = example.com) and samesite = lax \
+             and expiry > 2020-01-01",
+        )
+        .unwrap();
+        assert!(!expr.matches(&soon));
+        assert!(expr.matches(&later));
+        assert!(expr.matches(&other));
+        // Comparisons with unset fields are false
+        let expr = FilterExpr::parse("container != 'Work'").unwrap();
+        assert!(!expr.matches(&other));
+        let expr = FilterExpr::parse("not container = 'Work'").unwrap();
+        assert!(expr.matches(&other));
+
+        assert_eq!(
+            FilterExpr::parse("host ~ google and expiry <").unwrap_err(),
+            "Invalid filter at position 27: Expected a value\n  \
+             host ~ google and expiry <\n                            ^"
+        );
+        for (expr, error) in [
+            ("hots = x", "Unknown field 'hots'"),
+            ("expiry < tomorrow", "Invalid date 'tomorrow'"),
+            (
+                "expiry < now+9999999999999999d",
+                "Invalid date 'now+9999999999999999d'",
+            ),
+            ("secure > true", "Secure can only be compared with = or !="),
+            ("host ~ '('", "Invalid regex"),
+            ("(secure", "Expected ')'"),
+            ("secure httponly", "Expected 'and' or 'or'"),
+            ("name = 'abc", "Unterminated string"),
+        ] {
+            let err = FilterExpr::parse(expr).unwrap_err();
+            assert!(err.contains(error), "{expr}: {err}");
+        }
+    }
+}
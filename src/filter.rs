@@ -0,0 +1,253 @@
+use glob::{MatchOptions, Pattern};
+use std::path::Path;
+
+use crate::cookie::Cookie;
+use crate::cookie_db::CookieDB;
+use crate::expr::FilterExpr;
+
+/// Criteria that a cookie needs to fulfill to be included by the
+/// `cookies`, `clean` and `tui` subcommands (among others). Empty strings
+/// and `None` values are not used for filtering.
+#[derive(Debug, Default)]
+pub struct CookieFilter {
+    /// Substring that the host of the cookie needs to contain
+    pub domain: String,
+    /// See `Cookie::in_container()`
+    pub container: String,
+    /// Only include partitioned (`Some(true)`) or
+    /// unpartitioned (`Some(false)`) cookies
+    pub partitioned: Option<bool>,
+    /// The top-level site that the cookie needs to be partitioned under
+    pub partition_key: String,
+    /// A `--filter` expression
+    pub expr: Option<FilterExpr>,
+}
+
+impl CookieFilter {
+    /// Check if a cookie fulfills all criteria of the filter
+    pub fn matches(&self, cookie: &Cookie) -> bool {
+        if !self.domain.is_empty() && !cookie.host.contains(&self.domain) {
+            return false;
+        }
+        if !self.container.is_empty() && !cookie.in_container(&self.container) {
+            return false;
+        }
+        if self.expr.as_ref().is_some_and(|e| !e.matches(cookie)) {
+            return false;
+        }
+        let partition_key = cookie.partition_key();
+        if let Some(partitioned) = self.partitioned {
+            if partition_key.is_some() != partitioned {
+                return false;
+            }
+        }
+        if !self.partition_key.is_empty() {
+            return partition_key.is_some_and(|key| {
+                Self::is_same_site(&key, &self.partition_key)
+            });
+        }
+        true
+    }
+
+    /// Whether the filter compares decrypted cookie values
+    pub fn uses_value(&self) -> bool {
+        self.expr.as_ref().is_some_and(|e| e.uses_field("Value"))
+    }
+
+    /// Compare a partition key, e.g. `https://example.com`, with a site
+    /// given on the command line, the scheme of the site is optional
+    fn is_same_site(partition_key: &str, site: &str) -> bool {
+        if site.contains("://") {
+            partition_key == site
+        } else {
+            partition_key
+                .split_once("://")
+                .is_some_and(|(_, host)| host == site)
+        }
+    }
+}
+
+/// Criteria for which of the discovered cookie databases to include.
+/// Profiles and browsers can be given as exact names or glob patterns.
+#[derive(Debug, Default)]
+pub struct ProfileFilter {
+    /// Profile names or parts of profile paths to include
+    pub profiles: Vec<String>,
+    /// Profile names or parts of profile paths to skip
+    pub exclude_profiles: Vec<String>,
+    /// Browser names to include, e.g. `firefox`
+    pub browsers: Vec<String>,
+}
+
+impl ProfileFilter {
+    /// True if the filter would not exclude any database
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+            && self.exclude_profiles.is_empty()
+            && self.browsers.is_empty()
+    }
+
+    /// Drop all databases that are not selected by the filter.
+    /// Returns an error for the first `--profile` or `--browser`
+    /// that does not match anything.
+    pub fn apply(&self, cookie_dbs: &mut Vec<CookieDB>) -> Result<(), String> {
+        if !self.browsers.is_empty() {
+            for browser in self.browsers.iter() {
+                if !cookie_dbs.iter().any(|c| name_matches(browser, &c.browser))
+                {
+                    return Err(format!(
+                        "No browser matching '{browser}' found"
+                    ));
+                }
+            }
+            cookie_dbs.retain(|c| {
+                self.browsers.iter().any(|b| name_matches(b, &c.browser))
+            });
+        }
+
+        if !self.profiles.is_empty() {
+            let mut selected = vec![false; cookie_dbs.len()];
+            for profile in self.profiles.iter() {
+                let matches = Self::profile_matches(profile, cookie_dbs);
+                if !matches.iter().any(|m| *m) {
+                    return Err(format!(
+                        "No profile matching '{profile}' found"
+                    ));
+                }
+                selected.iter_mut().zip(matches).for_each(|(s, m)| *s |= m);
+            }
+            let mut selected = selected.into_iter();
+            cookie_dbs.retain(|_| selected.next().unwrap_or(false));
+        }
+
+        for profile in self.exclude_profiles.iter() {
+            let mut matches =
+                Self::profile_matches(profile, cookie_dbs).into_iter();
+            cookie_dbs.retain(|_| !matches.next().unwrap_or(false));
+        }
+        Ok(())
+    }
+
+    /// Determine which databases a `--profile` argument refers to.
+    /// Profiles are matched on their name if possible and on their
+    /// path otherwise.
+    fn profile_matches(profile: &str, cookie_dbs: &[CookieDB]) -> Vec<bool> {
+        let by_name: Vec<bool> = cookie_dbs
+            .iter()
+            .map(|c| {
+                c.profile
+                    .as_ref()
+                    .is_some_and(|p| name_matches(profile, &p.name))
+            })
+            .collect();
+        if by_name.iter().any(|m| *m) {
+            return by_name;
+        }
+        cookie_dbs
+            .iter()
+            .map(|c| path_matches(profile, &c.path))
+            .collect()
+    }
+}
+
+/// Check if a string contains any glob metacharacters
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Case insensitive comparison of a name against an exact name
+/// or a glob pattern
+fn name_matches(pattern: &str, name: &str) -> bool {
+    if is_glob(pattern) {
+        let options = MatchOptions {
+            case_sensitive: false,
+            ..MatchOptions::new()
+        };
+        Pattern::new(pattern).is_ok_and(|p| p.matches_with(name, options))
+    } else {
+        pattern.eq_ignore_ascii_case(name)
+    }
+}
+
+/// Check if a path contains a substring. Glob patterns are matched against
+/// the full path and the name of the profile directory.
+fn path_matches(pattern: &str, path: &Path) -> bool {
+    if is_glob(pattern) {
+        let Ok(pattern) = Pattern::new(pattern) else {
+            return false;
+        };
+        let profile_dir = path.parent().and_then(|p| p.file_name());
+        pattern.matches_path(path)
+            || profile_dir
+                .is_some_and(|d| pattern.matches(&d.to_string_lossy()))
+    } else {
+        path.to_string_lossy().contains(pattern)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::cookie_db::CookieDB;
+    use crate::filter::{CookieFilter, ProfileFilter};
+    use crate::profiles::ProfileInfo;
+    use crate::util::DbType;
+
+    #[test]
+    fn test_is_same_site() {
+        let key = "https://example.com";
+        assert!(CookieFilter::is_same_site(key, "example.com"));
+        assert!(CookieFilter::is_same_site(key, "https://example.com"));
+        assert!(!CookieFilter::is_same_site(key, "http://example.com"));
+        assert!(!CookieFilter::is_same_site(key, "ample.com"));
+    }
+
+    #[test]
+    fn test_profile_filter() {
+        let new_db = |path: &str, browser: &str, name: Option<&str>| {
+            let typing = if browser == "Firefox" {
+                DbType::Firefox
+            } else {
+                DbType::Chrome
+            };
+            let mut cdb = CookieDB::new(PathBuf::from(path), typing, browser);
+            cdb.profile = name.map(|n| ProfileInfo {
+                name: n.to_string(),
+                is_default: false,
+            });
+            cdb
+        };
+        let paths = |filter: ProfileFilter| -> Result<Vec<String>, String> {
+            let mut cookie_dbs = vec![
+                new_db("/ff/abc.work/cookies.sqlite", "Firefox", Some("Work")),
+                new_db("/ff/def.test/cookies.sqlite", "Firefox", Some("Test")),
+                new_db("/brave/Default/Cookies", "Brave", None),
+            ];
+            filter.apply(&mut cookie_dbs)?;
+            Ok(cookie_dbs.iter().map(|c| c.path_short()).collect())
+        };
+
+        let filter = ProfileFilter {
+            profiles: vec!["work".to_string(), "brave".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            paths(filter).unwrap(),
+            vec!["/ff/abc.work", "/brave/Default"]
+        );
+
+        let filter = ProfileFilter {
+            browsers: vec!["firefox".to_string()],
+            exclude_profiles: vec!["*.test".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(paths(filter).unwrap(), vec!["/ff/abc.work"]);
+
+        let filter = ProfileFilter {
+            profiles: vec!["missing".to_string()],
+            ..Default::default()
+        };
+        assert!(paths(filter).is_err());
+    }
+}
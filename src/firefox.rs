@@ -0,0 +1,198 @@
+use std::path::Path;
+
+use crate::container::{container_name, load_containers};
+use crate::cookie::Cookie;
+use crate::error::CookieError;
+use crate::session_store::load_session_cookies;
+use crate::store::{Capabilities, CookieStore, Schema, SqliteTable};
+
+/// The `cookies.sqlite` database of a Firefox profile
+pub struct FirefoxStore {
+    table: SqliteTable,
+}
+
+impl FirefoxStore {
+    pub fn new(path: &Path) -> Self {
+        FirefoxStore {
+            table: SqliteTable {
+                path: path.to_path_buf(),
+                table: "moz_cookies",
+                field_idx: 1,
+                // Timestamps are stored as UNIX epoch microseconds,
+                // except for the expiry which is stored in seconds
+                epoch_offset: 0,
+                seconds_fields: &["Expiry"],
+                encrypted_column: None,
+                version_query: "PRAGMA user_version;",
+            },
+        }
+    }
+
+    /// Add session cookies from the Firefox session file of the profile
+    /// that have not been written to the database
+    fn merge_session_cookies(&self, cookies: &mut Vec<Cookie>) {
+        let Some(profile_dir) = self.table.path.parent() else {
+            return;
+        };
+        for session_cookie in load_session_cookies(profile_dir) {
+            if !cookies.iter().any(|c| c.is_same_cookie(&session_cookie)) {
+                cookies.push(session_cookie);
+            }
+        }
+    }
+
+    /// Set the container name of each cookie that belongs
+    /// to a Firefox container
+    fn resolve_containers(&self, cookies: &mut [Cookie]) {
+        let containers = match self.table.path.parent() {
+            Some(profile_dir) => load_containers(profile_dir),
+            None => return,
+        };
+        for c in cookies.iter_mut() {
+            let user_context_id = c.origin().user_context_id;
+            if user_context_id != 0 {
+                c.container =
+                    Some(container_name(&containers, user_context_id));
+            }
+        }
+    }
+}
+
+impl CookieStore for FirefoxStore {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            writable: true,
+            encrypted: false,
+        }
+    }
+
+    fn schema(&self) -> Result<Schema, CookieError> {
+        self.table.schema()
+    }
+
+    fn load(&self) -> Result<Vec<Cookie>, CookieError> {
+        let (mut cookies, _) = self.table.load()?;
+        self.merge_session_cookies(&mut cookies);
+        self.resolve_containers(&mut cookies);
+        Ok(cookies)
+    }
+
+    fn insert(&self, cookies: &[Cookie]) -> Result<(), CookieError> {
+        self.table.insert(cookies)
+    }
+
+    fn update(&self, cookies: &[Cookie]) -> Result<(), CookieError> {
+        self.table.update(cookies)
+    }
+
+    /// Cookies from a session file are not part of the database
+    /// and are skipped
+    fn delete(&self, cookies: &[Cookie]) -> Result<(), CookieError> {
+        let cookies: Vec<Cookie> = cookies
+            .iter()
+            .filter(|c| !c.from_session_store)
+            .cloned()
+            .collect();
+        self.table.delete(&cookies)
+    }
+
+    fn sql_select(
+        &self,
+        conn: &rusqlite::Connection,
+        schema: &str,
+    ) -> Result<Option<String>, CookieError> {
+        self.table.sql_select(conn, schema).map(Some)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::path::PathBuf;
+
+    use crate::cookie::Cookie;
+    use crate::firefox::FirefoxStore;
+    use crate::store::CookieStore;
+
+    /// Create an empty `cookies.sqlite` in a new directory
+    /// with the given name under the temporary directory
+    pub fn create_firefox_db(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cookies.sqlite");
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "PRAGMA user_version = 14;
+             CREATE TABLE moz_cookies (id INTEGER PRIMARY KEY,
+                originAttributes TEXT NOT NULL DEFAULT '', name TEXT,
+                value TEXT, host TEXT, path TEXT, expiry INTEGER,
+                lastAccessed INTEGER, creationTime INTEGER,
+                isSecure INTEGER, isHttpOnly INTEGER,
+                inBrowserElement INTEGER DEFAULT 0,
+                sameSite INTEGER DEFAULT 0, rawSameSite INTEGER DEFAULT 0,
+                schemeMap INTEGER DEFAULT 0,
+                isPartitionedAttributeSet INTEGER DEFAULT 0);",
+        )
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn test_expiry_round_trip() {
+        let path = create_firefox_db("cookiecutter_test_firefox");
+        let store = FirefoxStore::new(&path);
+        let mut cookie = Cookie {
+            host: ".example.com".to_string(),
+            name: "sid".to_string(),
+            path: "/".to_string(),
+            creation: 1_700_000_000,
+            expiry: 1_900_000_000,
+            last_access: 1_700_000_000,
+            origin_attributes: Some(String::new()),
+            ..Default::default()
+        };
+        store.insert(&[cookie.clone()]).unwrap();
+
+        // The expiry is stored in seconds, other timestamps in microseconds
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        let (expiry, creation): (i64, i64) = conn
+            .query_row("SELECT expiry, creationTime FROM moz_cookies", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(expiry, 1_900_000_000);
+        assert_eq!(creation, 1_700_000_000_000_000);
+
+        let cookies = store.load().unwrap();
+        assert_eq!(cookies[0].expiry, 1_900_000_000);
+        assert_eq!(cookies[0].creation, 1_700_000_000);
+
+        cookie.expiry = 2_000_000_000;
+        store.update(&[cookie]).unwrap();
+        let cookies = store.load().unwrap();
+        assert_eq!(cookies[0].expiry, 2_000_000_000);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_load_expiry() {
+        // A row as written by Firefox
+        let path = create_firefox_db("cookiecutter_test_firefox_load");
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute(
+            "INSERT INTO moz_cookies (name, value, host, path, expiry,
+                lastAccessed, creationTime, isSecure, isHttpOnly)
+             VALUES ('sid', 'abc', '.example.com', '/', 1900000000,
+                1700000000000000, 1600000000000000, 1, 1)",
+            [],
+        )
+        .unwrap();
+
+        let cookies = FirefoxStore::new(&path).load().unwrap();
+        assert_eq!(cookies[0].expiry, 1_900_000_000);
+        assert_eq!(cookies[0].creation, 1_600_000_000);
+        assert_eq!(cookies[0].last_access, 1_700_000_000);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
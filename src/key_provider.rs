@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use zbus::blocking::Connection;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use crate::config::{
+    LIBSECRET_DESKTOPS, SECRET_SERVICE_NAME, SECRET_SERVICE_PATH,
+};
+
+/// A source for the password that Chromium derives the key for cookie
+/// values from, i.e. the "Chrome Safe Storage" password on macOS or the
+/// password that Chromium stores in the keyring on Linux
+pub trait KeyProvider: Debug + Send + Sync {
+    /// Look up the password for the given browser, e.g. "Chrome"
+    fn password(&self, browser: &str) -> Result<String, String>;
+}
+
+/// A password given on the command line
+pub struct StaticPassword(pub String);
+
+impl Debug for StaticPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StaticPassword(<redacted>)")
+    }
+}
+
+impl KeyProvider for StaticPassword {
+    fn password(&self, _browser: &str) -> Result<String, String> {
+        Ok(self.0.to_owned())
+    }
+}
+
+/// A password read from a file, a trailing newline is ignored
+#[derive(Debug)]
+pub struct FilePassword(pub PathBuf);
+
+impl KeyProvider for FilePassword {
+    fn password(&self, _browser: &str) -> Result<String, String> {
+        let content = std::fs::read_to_string(&self.0)
+            .map_err(|e| format!("{}: {}", self.0.display(), e))?;
+        Ok(content.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
+
+/// A password read from an environment variable
+#[derive(Debug)]
+pub struct EnvPassword(pub String);
+
+impl KeyProvider for EnvPassword {
+    fn password(&self, _browser: &str) -> Result<String, String> {
+        std::env::var(&self.0).map_err(|e| format!("${}: {}", self.0, e))
+    }
+}
+
+/// The application attribute and label that a browser uses for its entry
+/// in the keyring, e.g. ("chrome", "Chrome")
+fn keyring_names(browser: &str) -> (String, String) {
+    match browser.to_lowercase().as_str() {
+        "edge" => ("microsoft-edge".to_string(), "Microsoft Edge".to_string()),
+        application => (application.to_string(), browser.to_string()),
+    }
+}
+
+/// A secret as returned by the Secret Service API:
+/// (session, parameters, value, content type)
+type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+/// The Secret Service D-Bus API (org.freedesktop.secrets), implemented
+/// by GNOME Keyring and KeePassXC among others. Chromium stores its
+/// password with an `application` attribute set to e.g. "chrome".
+#[derive(Debug, Default)]
+pub struct SecretService {
+    /// Connection to use instead of the session bus
+    connection: Option<Connection>,
+}
+
+impl SecretService {
+    #[cfg(test)]
+    fn with_connection(connection: Connection) -> Self {
+        SecretService {
+            connection: Some(connection),
+        }
+    }
+
+    fn lookup(&self, conn: &Connection, browser: &str) -> zbus::Result<String> {
+        let (application, _) = keyring_names(browser);
+        let proxy = zbus::blocking::Proxy::new(
+            conn,
+            SECRET_SERVICE_NAME,
+            SECRET_SERVICE_PATH,
+            "org.freedesktop.Secret.Service",
+        )?;
+        // Secrets are transferred unencrypted over the bus with the
+        // "plain" algorithm, the same as libsecret does for local buses
+        let (_, session): (OwnedValue, OwnedObjectPath) =
+            proxy.call("OpenSession", &("plain", Value::from("")))?;
+
+        let attributes = HashMap::from([("application", application.as_str())]);
+        let (unlocked, locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) =
+            proxy.call("SearchItems", &(attributes,))?;
+        if unlocked.is_empty() {
+            let reason = if locked.is_empty() {
+                "No keyring entry found"
+            } else {
+                "The keyring entry is locked"
+            };
+            return Err(zbus::Error::Failure(format!(
+                "{reason} for application '{application}'"
+            )));
+        }
+
+        let secrets: HashMap<OwnedObjectPath, Secret> =
+            proxy.call("GetSecrets", &(unlocked, &session))?;
+        proxy.connection().call_method(
+            Some(SECRET_SERVICE_NAME),
+            session.as_ref(),
+            Some("org.freedesktop.Secret.Session"),
+            "Close",
+            &(),
+        )?;
+
+        secrets
+            .into_values()
+            .next()
+            .map(|(_, _, value, _)| String::from_utf8_lossy(&value).to_string())
+            .ok_or(zbus::Error::Failure("Empty keyring entry".to_string()))
+    }
+}
+
+impl KeyProvider for SecretService {
+    fn password(&self, browser: &str) -> Result<String, String> {
+        let result = match &self.connection {
+            Some(conn) => self.lookup(conn, browser),
+            None => Connection::session()
+                .and_then(|conn| self.lookup(&conn, browser)),
+        };
+        result.map_err(|e| format!("Secret Service: {e}"))
+    }
+}
+
+/// The KDE wallet, the D-Bus service differs between KDE versions
+#[derive(Debug)]
+pub struct KWallet {
+    /// Major version of `kwalletd`, 4 uses an unversioned service name
+    pub version: u8,
+}
+
+impl KWallet {
+    fn lookup(&self, browser: &str) -> zbus::Result<String> {
+        let (_, label) = keyring_names(browser);
+        let daemon = match self.version {
+            4 => "kwalletd".to_string(),
+            v => format!("kwalletd{v}"),
+        };
+        let conn = Connection::session()?;
+        let proxy = zbus::blocking::Proxy::new(
+            &conn,
+            format!("org.kde.{daemon}"),
+            format!("/modules/{daemon}"),
+            "org.kde.KWallet",
+        )?;
+        let app_id = env!("CARGO_PKG_NAME");
+
+        let wallet: String = proxy.call("networkWallet", &())?;
+        let handle: i32 = proxy.call("open", &(wallet, 0i64, app_id))?;
+        if handle < 0 {
+            return Err(zbus::Error::Failure(
+                "Failed to open the wallet".to_string(),
+            ));
+        }
+        let password: Result<String, _> = proxy.call(
+            "readPassword",
+            &(
+                handle,
+                format!("{label} Keys"),
+                format!("{label} Safe Storage"),
+                app_id,
+            ),
+        );
+        let _: i32 = proxy.call("close", &(handle, false, app_id))?;
+
+        match password? {
+            p if p.is_empty() => Err(zbus::Error::Failure(format!(
+                "No wallet entry found for '{label} Safe Storage'"
+            ))),
+            p => Ok(p),
+        }
+    }
+}
+
+impl KeyProvider for KWallet {
+    fn password(&self, browser: &str) -> Result<String, String> {
+        self.lookup(browser).map_err(|e| format!("KWallet: {e}"))
+    }
+}
+
+/// Where Chromium keeps its password on Linux, the values match
+/// Chromium's own `--password-store` option
+#[derive(Debug, Clone, PartialEq, ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PasswordStore {
+    /// Detect the store from the desktop environment like Chromium does
+    Auto,
+    /// No keyring, only `v10` values can be decrypted
+    Basic,
+    GnomeLibsecret,
+    Kwallet,
+    Kwallet5,
+    Kwallet6,
+}
+
+impl PasswordStore {
+    /// Resolve `Auto` from $XDG_CURRENT_DESKTOP and $KDE_SESSION_VERSION
+    fn detect(&self) -> PasswordStore {
+        if *self != PasswordStore::Auto {
+            return self.clone();
+        }
+        let desktop = std::env::var("XDG_CURRENT_DESKTOP").unwrap_or_default();
+        let desktops: Vec<String> =
+            desktop.split(':').map(|d| d.to_lowercase()).collect();
+        if desktops.iter().any(|d| d == "kde") {
+            match std::env::var("KDE_SESSION_VERSION").as_deref() {
+                Ok("6") => PasswordStore::Kwallet6,
+                Ok("5") => PasswordStore::Kwallet5,
+                _ => PasswordStore::Kwallet,
+            }
+        } else if desktops
+            .iter()
+            .any(|d| LIBSECRET_DESKTOPS.contains(&d.as_str()))
+        {
+            PasswordStore::GnomeLibsecret
+        } else {
+            PasswordStore::Basic
+        }
+    }
+
+    /// The provider for the store, `None` for `Basic`
+    pub fn provider(&self) -> Option<Box<dyn KeyProvider>> {
+        match self.detect() {
+            PasswordStore::GnomeLibsecret => {
+                Some(Box::new(SecretService::default()))
+            }
+            PasswordStore::Kwallet => Some(Box::new(KWallet { version: 4 })),
+            PasswordStore::Kwallet5 => Some(Box::new(KWallet { version: 5 })),
+            PasswordStore::Kwallet6 => Some(Box::new(KWallet { version: 6 })),
+            PasswordStore::Auto | PasswordStore::Basic => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::os::unix::net::UnixStream;
+
+    use zbus::blocking::connection::Builder;
+    use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue};
+
+    use crate::key_provider::{
+        KeyProvider, Secret, SecretService, StaticPassword,
+    };
+
+    /// Mock of the parts of org.freedesktop.Secret.Service that are
+    /// used for the lookup, holding a single entry for "chromium"
+    struct MockService;
+
+    #[zbus::interface(name = "org.freedesktop.Secret.Service")]
+    impl MockService {
+        fn open_session(
+            &self,
+            _algorithm: &str,
+            _input: OwnedValue,
+        ) -> (OwnedValue, OwnedObjectPath) {
+            (OwnedValue::from(0u8), path("/session/1"))
+        }
+
+        fn search_items(
+            &self,
+            attributes: HashMap<String, String>,
+        ) -> (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) {
+            match attributes.get("application").map(|a| a.as_str()) {
+                Some("chromium") => (vec![path("/item/1")], vec![]),
+                Some("brave") => (vec![], vec![path("/item/2")]),
+                _ => (vec![], vec![]),
+            }
+        }
+
+        fn get_secrets(
+            &self,
+            items: Vec<OwnedObjectPath>,
+            session: OwnedObjectPath,
+        ) -> HashMap<OwnedObjectPath, Secret> {
+            items
+                .into_iter()
+                .map(|item| {
+                    let secret = (
+                        session.clone(),
+                        vec![],
+                        b"s3cr3t".to_vec(),
+                        "text/plain".to_string(),
+                    );
+                    (item, secret)
+                })
+                .collect()
+        }
+    }
+
+    struct MockSession;
+
+    #[zbus::interface(name = "org.freedesktop.Secret.Session")]
+    impl MockSession {
+        fn close(&self) {}
+    }
+
+    fn path(p: &str) -> OwnedObjectPath {
+        ObjectPath::try_from(p).unwrap().into()
+    }
+
+    #[test]
+    fn test_secret_service() {
+        let (server_stream, client_stream) = UnixStream::pair().unwrap();
+        let guid = zbus::Guid::generate();
+        let server = std::thread::spawn(move || {
+            Builder::async_io_unix_stream(server_stream)
+                .server(guid)
+                .unwrap()
+                .p2p()
+                .serve_at("/org/freedesktop/secrets", MockService)
+                .unwrap()
+                .serve_at("/session/1", MockSession)
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = Builder::async_io_unix_stream(client_stream)
+            .p2p()
+            .build()
+            .unwrap();
+        let _server = server.join().unwrap();
+
+        let provider = SecretService::with_connection(client);
+        assert_eq!(provider.password("Chromium"), Ok("s3cr3t".to_string()));
+        assert!(provider.password("Brave").unwrap_err().contains("locked"));
+        assert!(provider.password("Vivaldi").is_err());
+    }
+
+    #[test]
+    fn test_static_password_debug() {
+        let provider = StaticPassword("hunter2".to_string());
+        assert_eq!(format!("{provider:?}"), "StaticPassword(<redacted>)");
+        assert_eq!(provider.password("Chrome"), Ok("hunter2".to_string()));
+    }
+}
@@ -0,0 +1,186 @@
+use std::fmt;
+
+use crate::config::LINT_MAX_LIFETIME_SECS;
+use crate::cookie::Cookie;
+use crate::cookie_db::CookieDB;
+use crate::util::registrable_domain;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => f.pad("warning"),
+            Severity::Error => f.pad("error"),
+        }
+    }
+}
+
+/// A problem with the attributes of a cookie
+#[derive(Debug, PartialEq)]
+pub struct LintIssue {
+    pub severity: Severity,
+    /// Short identifier of the check, e.g. `host-prefix`
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl LintIssue {
+    fn error(rule: &'static str, message: &str) -> Self {
+        LintIssue {
+            severity: Severity::Error,
+            rule,
+            message: message.to_string(),
+        }
+    }
+
+    fn warning(rule: &'static str, message: &str) -> Self {
+        LintIssue {
+            severity: Severity::Warning,
+            rule,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Check the attributes of a cookie against the requirements and
+/// recommendations of RFC 6265bis
+pub fn lint_cookie(c: &Cookie) -> Vec<LintIssue> {
+    let mut issues = vec![];
+    // Domain cookies are stored with a leading '.'
+    let has_domain = c.host.starts_with('.');
+
+    if !c.secure && c.is_https_only() {
+        issues.push(LintIssue::error(
+            "missing-secure",
+            "Only set over HTTPS but not Secure",
+        ));
+    }
+    if c.is_samesite_none_insecure() {
+        issues.push(LintIssue::error(
+            "samesite-none-insecure",
+            "SameSite=None without Secure",
+        ));
+    }
+    if c.has_session_name() && !c.http_only {
+        issues.push(LintIssue::error(
+            "session-not-httponly",
+            "Session-like name without HttpOnly",
+        ));
+    }
+    if c.name.starts_with("__Host-") {
+        if !c.secure {
+            issues.push(LintIssue::error("host-prefix", "Not Secure"));
+        }
+        if c.path != "/" {
+            issues.push(LintIssue::error("host-prefix", "Path is not /"));
+        }
+        if has_domain {
+            issues.push(LintIssue::error(
+                "host-prefix",
+                "Has a Domain attribute",
+            ));
+        }
+    }
+    if c.name.starts_with("__Secure-") && !c.secure {
+        issues.push(LintIssue::error("secure-prefix", "Not Secure"));
+    }
+    if has_domain && c.host[1..] == registrable_domain(&c.host) {
+        issues.push(LintIssue::warning(
+            "broad-domain",
+            &format!("Sent to all subdomains of {}", &c.host[1..]),
+        ));
+    }
+    if c.expiry > 0 && c.expiry - c.creation > LINT_MAX_LIFETIME_SECS {
+        issues.push(LintIssue::warning(
+            "long-lifetime",
+            &format!(
+                "Expires after {} days",
+                (c.expiry - c.creation) / (24 * 60 * 60)
+            ),
+        ));
+    }
+    issues
+}
+
+/// Print the issues of the loaded cookies of each database. Returns false
+/// if there are errors, or any issues at all if `strict` is set.
+pub fn lint(cookie_dbs: &[CookieDB], strict: bool) -> bool {
+    let mut counts = [0, 0];
+    for cdb in cookie_dbs {
+        let mut lines = vec![];
+        for c in cdb.cookies.iter() {
+            let mut cookie = format!("{} {}", c.host, c.name);
+            if let Some(container) = &c.container {
+                cookie += &format!(" [{container}]");
+            }
+            for issue in lint_cookie(c) {
+                counts[issue.severity as usize] += 1;
+                lines.push(format!(
+                    "  {:<7}  {cookie}  {}: {}",
+                    issue.severity, issue.rule, issue.message
+                ));
+            }
+        }
+        if !lines.is_empty() {
+            println!("{}", cdb.display_name());
+            lines.iter().for_each(|l| println!("{l}"));
+        }
+    }
+    let [warnings, errors] = counts;
+    println!("{errors} errors, {warnings} warnings");
+    errors == 0 && (!strict || warnings == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cookie::tests::cookie;
+    use crate::cookie::Cookie;
+    use crate::lint::{lint_cookie, Severity};
+
+    fn rules(c: &Cookie) -> Vec<&'static str> {
+        lint_cookie(c).iter().map(|i| i.rule).collect()
+    }
+
+    #[test]
+    fn test_lint_cookie() {
+        // Set over HTTPS with a lifetime of 30 days
+        let valid = |host: &str, name: &str| Cookie {
+            creation: 1_700_000_000,
+            expiry: 1_700_000_000 + 30 * 24 * 60 * 60,
+            http_only: true,
+            scheme_map: Some(2),
+            ..cookie(host, name, "")
+        };
+        let c = valid("app.example.com", "__Host-id");
+        assert!(lint_cookie(&c).is_empty());
+
+        let mut c = valid(".app.example.com", "__Host-sid");
+        c.path = "/app".to_string();
+        c.secure = false;
+        c.http_only = false;
+        c.samesite = 0;
+        assert_eq!(
+            rules(&c),
+            [
+                "missing-secure",
+                "samesite-none-insecure",
+                "session-not-httponly",
+                "host-prefix",
+                "host-prefix",
+                "host-prefix"
+            ]
+        );
+
+        let mut c = valid(".example.com", "__Secure-pref");
+        c.expiry = c.creation + 500 * 24 * 60 * 60;
+        let issues = lint_cookie(&c);
+        assert_eq!(rules(&c), ["broad-domain", "long-lifetime"]);
+        assert!(issues.iter().all(|i| i.severity == Severity::Warning));
+        assert_eq!(issues[1].message, "Expires after 500 days");
+    }
+}
@@ -2,28 +2,65 @@ use std::collections::HashSet;
 use std::path;
 
 use clap::{CommandFactory, Parser};
+use rayon::prelude::*;
 
 //=== Project imports ===//
+mod audit;
+mod browser;
+mod chromium;
 mod config;
+mod container;
 mod cookie;
 mod cookie_db;
+mod decrypt;
+mod diff;
+mod discovery;
+mod error;
+mod expr;
+mod filter;
+mod firefox;
+mod key_provider;
+mod lint;
+mod profiles;
+mod safari;
+mod session_store;
+mod snapshot;
+mod sort;
+mod sql;
 mod state;
+mod stats;
+mod store;
 mod tui;
 mod util;
+mod watch;
+mod watcher;
+use crate::audit::{
+    report_html, report_json, report_text, ProfileReport, ReportFormat,
+    TrackerList,
+};
 use crate::config::{
-    Args, Config, ALL_FIELDS, CONFIG, COOKIE_FIELDS, DEBUG_LOG,
+    Args, Config, ALL_FIELDS, COMMON_FIELDS, CONFIG, DEBUG_LOG,
+    OPTIONAL_FIELDS,
 };
 use crate::cookie_db::CookieDB;
+use crate::discovery::cookie_dbs_from_profiles;
+use crate::lint::lint;
+use crate::snapshot::{print_diff, Snapshot};
+use crate::sql::{open_unified, query, OutputFormat};
+use crate::stats::print_stats;
 use crate::tui::run;
+use crate::watch::watch;
 use crate::util::{
-    cookie_db_type, cookie_dbs_from_profiles, parse_whitelist,
-    process_is_running,
+    cookie_db_type, expand_file_args, parse_whitelist, process_is_running,
 };
 
 fn main() -> Result<(), ()> {
     // Load command line configuration arguments into a global
     let args: Args = Args::parse();
-    let cfg = Config::from_args(&args);
+    let cfg = Config::from_args(&args).unwrap_or_else(|err| {
+        eprintln!("ERROR: {err}");
+        std::process::exit(Config::default().err_exit);
+    });
     CONFIG.set(cfg).unwrap();
     if Config::global().debug {
         eprintln!("{:#?}", Config::global());
@@ -34,82 +71,110 @@ fn main() -> Result<(), ()> {
     }
 
     let mut cookie_dbs: HashSet<CookieDB> = HashSet::new();
+    let profile_filter = &Config::global().profile_filter;
 
-    // Parse a custom db if a --file was provided
-    if args.file != "" {
-        let custom_db_path = path::PathBuf::try_from(&args.file)
-            .expect("Could not create PathBuf from provided --file");
-        let typing = cookie_db_type(&custom_db_path.as_path())
-            .expect("Failed to determine database type of --file argument");
-        cookie_dbs.insert(CookieDB {
-            path: custom_db_path,
-            typing,
-            cookies: vec![],
-        });
-    } else {
+    // Only skip discovery if --file was provided without any selectors
+    if args.file.is_empty()
+        || !profile_filter.profiles.is_empty()
+        || !profile_filter.browsers.is_empty()
+    {
         // Fetch a set of all cookie dbs on the system
         cookie_dbs_from_profiles(&mut cookie_dbs);
     }
     let mut cookie_dbs = Vec::from_iter(cookie_dbs);
-    cookie_dbs.sort();
 
-    // Explicitly note if an invalid --profile was specified
-    if args.profile != ""
-        && !cookie_dbs.iter().any(|c| {
-            c.path.to_string_lossy().to_owned().contains(&args.profile)
-        })
-    {
-        eprintln!("ERROR: No profile matching '{}' found", args.profile);
+    // Only keep databases from the requested profiles and browsers,
+    // explicitly note if an invalid --profile or --browser was specified
+    if !profile_filter.is_empty() {
+        if let Err(err) = profile_filter.apply(&mut cookie_dbs) {
+            eprintln!("ERROR: {err}");
+            std::process::exit(Config::global().err_exit);
+        }
+    }
+
+    // Add custom dbs if any --file was provided
+    let custom_db_paths = expand_file_args(&args.file).unwrap_or_else(|err| {
+        eprintln!("ERROR: {err}");
         std::process::exit(Config::global().err_exit);
+    });
+    for custom_db_path in custom_db_paths {
+        let typing = cookie_db_type(custom_db_path.as_path())
+            .unwrap_or_else(|err| {
+                eprintln!("ERROR: {}: {err}", custom_db_path.display());
+                std::process::exit(Config::global().err_exit);
+            });
+        let browser = typing.name();
+        let mut cookie_db = CookieDB::new(custom_db_path, typing, browser);
+        cookie_db.load_profile_info();
+        if !cookie_dbs.contains(&cookie_db) {
+            cookie_dbs.push(cookie_db);
+        }
     }
+    cookie_dbs.sort();
 
     if args.list_profiles {
         println!("Profiles with a cookie database:");
-        cookie_dbs.iter().for_each(|c| {
-            println!("  {}", c.path_short());
+        let names: Vec<String> = cookie_dbs
+            .iter()
+            .map(|c| match &c.profile {
+                Some(p) if p.is_default => format!("{} (default)", p.name),
+                Some(p) => p.name.to_owned(),
+                None => "-".to_string(),
+            })
+            .collect();
+        let browser_width = cookie_dbs.iter().map(|c| c.browser.len()).max();
+        let name_width = names.iter().map(|n| n.len()).max();
+        cookie_dbs.iter().zip(names.iter()).for_each(|(c, name)| {
+            println!(
+                "  {:<bw$}  {:<nw$}  {}",
+                c.browser,
+                name,
+                c.path_short(),
+                bw = browser_width.unwrap_or(0),
+                nw = name_width.unwrap_or(0),
+            );
+            if Config::global().debug {
+                match c.store().and_then(|s| s.schema()) {
+                    Ok(schema) => eprintln!("{:#?}", schema),
+                    Err(err) => eprintln!("ERROR: {err}"),
+                }
+            }
         });
     }
     //== Subcmd: cookies ==//
     else if Config::global().list_fields {
         println!("Valid fields:");
-        for field_name in COOKIE_FIELDS.keys() {
+        for field_name in COMMON_FIELDS.iter().chain(OPTIONAL_FIELDS) {
             println!("  {:?}", field_name);
         }
     } else if Config::global().fields != "" && cookie_dbs.len() > 0 {
         let multiple_fields = Config::global().fields.find(",").is_some()
             || Config::global().fields == ALL_FIELDS;
 
-        for mut cookie_db in cookie_dbs {
-            // Skip profiles if a specific --profile was passed
-            if args.profile != ""
-                && !cookie_db.path.to_string_lossy().contains(&args.profile)
-            {
-                continue;
-            }
+        // Load all fields from each cookie database, in parallel
+        cookie_dbs.par_iter_mut().for_each(|cookie_db| {
+            cookie_db.load_cookies().expect("Failed to load cookies");
+            cookie_db.retain_matching(&Config::global().filter);
+            cookie_db.sort_cookies(&Config::global().sort);
+        });
+
+        for cookie_db in cookie_dbs {
             // Skip profile headings if --no-heading
             if !Config::global().no_heading {
                 println!("{}", cookie_db.path_short());
             }
-            // Load all fields from each cookie database
-            cookie_db.load_cookies().expect("Failed to load cookies");
             let mut output_str = String::new();
 
             for c in cookie_db.cookies.iter() {
-                // Skip domains if a specific --domain was passed
-                if Config::global().domain == ""
-                    || c.host.contains(&Config::global().domain)
-                {
-                    output_str = output_str
-                        + &c.fields_as_str(
-                            &Config::global().fields,
-                            multiple_fields,
-                        )
-                        + "\n";
-
-                    if multiple_fields {
-                        // Skip blankline if only one field is being printed
-                        output_str = output_str + "\n"
-                    }
+                output_str += &c.fields_as_str(
+                    &Config::global().fields,
+                    multiple_fields,
+                );
+                output_str += "\n";
+
+                if multiple_fields {
+                    // Skip blankline if only one field is being printed
+                    output_str += "\n"
                 }
             }
             print!("{output_str}");
@@ -125,16 +190,30 @@ fn main() -> Result<(), ()> {
                 .expect("Failed to parse whitelist");
         }
 
-        for cookie_db in cookie_dbs {
-            // Skip profiles if a specific --profile was passed
-            if args.profile != ""
-                && !cookie_db.path.to_string_lossy().contains(&args.profile)
-            {
-                continue;
-            }
+        // Read-only databases are skipped below. Cookies are matched on
+        // their identity, values are only decrypted if the filter needs them
+        let decrypt = Config::global().filter.uses_value();
+        cookie_dbs
+            .par_iter_mut()
+            .filter(|cookie_db| !cookie_db.is_read_only())
+            .for_each(|cookie_db| {
+                if decrypt {
+                    cookie_db.load_cookies()
+                } else {
+                    cookie_db.load_cookies_encrypted()
+                }
+                .expect("Failed to load cookies");
+                cookie_db.retain_matching(&Config::global().filter);
+            });
+
+        for mut cookie_db in cookie_dbs {
             if whitelist.is_empty() {
                 println!("WARN: Empty whitelist!");
             }
+            if cookie_db.is_read_only() {
+                println!("Skipping read-only {}", cookie_db.path_short());
+                continue;
+            }
             println!("Cleaning {}", cookie_db.path_short());
             cookie_db
                 .clean(&whitelist, Config::global().apply)
@@ -157,10 +236,137 @@ fn main() -> Result<(), ()> {
                 .expect("Failed to create debug log");
         }
 
-        cookie_dbs
-            .iter_mut()
-            .for_each(|c| c.load_cookies().expect("Failed to load cookies"));
+        // Cookies are loaded once a profile is selected in the TUI
         run(cookie_dbs).expect("Failed to create TUI");
+    }
+    //== Subcmd: watch ==//
+    else if Config::global().watch {
+        cookie_dbs.par_iter_mut().for_each(|cookie_db| {
+            cookie_db.load_cookies().expect("Failed to load cookies");
+            cookie_db.retain_matching(&Config::global().filter);
+        });
+        watch(cookie_dbs);
+    }
+    //== Subcmd: snapshot ==//
+    else if let Some(name) = &Config::global().snapshot_save {
+        cookie_dbs.par_iter_mut().for_each(|cookie_db| {
+            cookie_db.load_cookies().expect("Failed to load cookies");
+        });
+        let snapshot = Snapshot::new(&cookie_dbs);
+        if let Err(err) = snapshot.save(name) {
+            eprintln!("ERROR: {err}");
+            std::process::exit(Config::global().err_exit);
+        }
+        println!(
+            "Saved {} cookies from {} profiles as '{name}'",
+            snapshot.cookie_count(),
+            snapshot.profiles.len()
+        );
+    } else if Config::global().snapshot_list {
+        let names = Snapshot::list().unwrap_or_else(|err| {
+            eprintln!("ERROR: {err}");
+            std::process::exit(Config::global().err_exit);
+        });
+        println!("Snapshots:");
+        let name_width = names.iter().map(|n| n.len()).max();
+        for name in names {
+            match Snapshot::load(&name) {
+                Ok(snapshot) => println!(
+                    "  {:<nw$}  {}  {} profiles, {} cookies",
+                    name,
+                    snapshot.created_str(),
+                    snapshot.profiles.len(),
+                    snapshot.cookie_count(),
+                    nw = name_width.unwrap_or(0),
+                ),
+                Err(err) => eprintln!("ERROR: {err}"),
+            }
+        }
+    }
+    //== Subcmd: diff ==//
+    else if let Some(from) = &Config::global().diff_from {
+        let load = |name: &str| {
+            Snapshot::load(name).unwrap_or_else(|err| {
+                eprintln!("ERROR: {err}");
+                std::process::exit(Config::global().err_exit);
+            })
+        };
+        let mut old = load(from);
+        let mut new = match &Config::global().diff_to {
+            Some(to) => load(to),
+            None => {
+                cookie_dbs.par_iter_mut().for_each(|cookie_db| {
+                    cookie_db.load_cookies().expect("Failed to load cookies");
+                });
+                Snapshot::new(&cookie_dbs)
+            }
+        };
+        // Compare only the selected profiles, two snapshots
+        // are compared in full unless profiles were selected
+        if Config::global().diff_to.is_none()
+            || !profile_filter.is_empty()
+            || !args.file.is_empty()
+        {
+            let paths: Vec<&path::Path> =
+                cookie_dbs.iter().map(|c| c.path.as_path()).collect();
+            old.retain_profiles(&paths);
+            new.retain_profiles(&paths);
+        }
+        print_diff(&old, &new, &Config::global().filter);
+    }
+    //== Subcmd: audit ==//
+    else if Config::global().audit {
+        let trackers = Config::global().trackers.as_ref().map(|path| {
+            TrackerList::load(path).unwrap_or_else(|err| {
+                eprintln!("ERROR: {err}");
+                std::process::exit(Config::global().err_exit);
+            })
+        });
+        cookie_dbs.par_iter_mut().for_each(|cookie_db| {
+            cookie_db.load_cookies().expect("Failed to load cookies");
+            cookie_db.retain_matching(&Config::global().filter);
+        });
+        let reports: Vec<ProfileReport> = cookie_dbs
+            .iter()
+            .map(|cookie_db| ProfileReport::new(cookie_db, trackers.as_ref()))
+            .collect();
+        match Config::global().report_format {
+            ReportFormat::Text => print!("{}", report_text(&reports)),
+            ReportFormat::Json => println!("{}", report_json(&reports)),
+            ReportFormat::Html => print!("{}", report_html(&reports)),
+        }
+    }
+    //== Subcmd: lint ==//
+    else if Config::global().lint {
+        cookie_dbs.par_iter_mut().for_each(|cookie_db| {
+            cookie_db.load_cookies().expect("Failed to load cookies");
+            cookie_db.retain_matching(&Config::global().filter);
+        });
+        if !lint(&cookie_dbs, Config::global().strict) {
+            std::process::exit(Config::global().err_exit);
+        }
+    }
+    //== Subcmd: stats ==//
+    else if Config::global().stats {
+        cookie_dbs.par_iter_mut().for_each(|cookie_db| {
+            cookie_db.load_cookies().expect("Failed to load cookies");
+            cookie_db.retain_matching(&Config::global().filter);
+        });
+        print_stats(&cookie_dbs, Config::global().top);
+    }
+    //== Subcmd: sql ==//
+    else if let Some(sql) = &Config::global().sql {
+        let result = open_unified(&cookie_dbs)
+            .and_then(|conn| query(&conn, sql))
+            .unwrap_or_else(|err| {
+                eprintln!("ERROR: {err}");
+                std::process::exit(Config::global().err_exit);
+            });
+        match Config::global().sql_format {
+            OutputFormat::Table => print!("{}", result.to_table()),
+            OutputFormat::Json => print!("{}", result.to_json()),
+            OutputFormat::Csv => print!("{}", result.to_csv()),
+        }
     } else {
         let mut args_cmd = Args::command();
         args_cmd.print_help().unwrap();
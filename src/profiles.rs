@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::config::{
+    CHROMIUM_LOCAL_STATE, FIREFOX_INSTALLS_INI, FIREFOX_PROFILES_INI,
+};
+
+/// Metadata about a browser profile from the browser's own profile list
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileInfo {
+    /// The name shown for the profile in the browser, e.g. "Work"
+    pub name: String,
+    /// Set for the profile that the browser opens by default
+    pub is_default: bool,
+}
+
+/// A section of an INI file with its key-value pairs
+type IniSection = (String, HashMap<String, String>);
+
+/// Minimal INI parser for `profiles.ini` and `installs.ini`,
+/// comments and lines outside of a section are ignored.
+fn parse_ini(content: &str) -> Vec<IniSection> {
+    let mut sections: Vec<IniSection> = vec![];
+    for line in content.lines().map(|l| l.trim()) {
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].to_string();
+            sections.push((name, HashMap::new()));
+        } else if line.starts_with(';') || line.starts_with('#') {
+            continue;
+        } else if let Some((key, value)) = line.split_once('=') {
+            if let Some((_, entries)) = sections.last_mut() {
+                entries
+                    .insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+    }
+    sections
+}
+
+/// Look up a Firefox profile in the `profiles.ini` file of the browser.
+/// The file is located next to the profile directory on Linux and one
+/// level above it (outside of `Profiles/`) on macOS and Windows.
+pub fn firefox_profile_info(profile_dir: &Path) -> Option<ProfileInfo> {
+    profile_dir
+        .ancestors()
+        .skip(1)
+        .take(2)
+        .find_map(|root| firefox_profile_info_in(root, profile_dir))
+}
+
+fn firefox_profile_info_in(
+    root: &Path,
+    profile_dir: &Path,
+) -> Option<ProfileInfo> {
+    let content = fs::read_to_string(root.join(FIREFOX_PROFILES_INI)).ok()?;
+    let mut sections = parse_ini(&content);
+
+    // The default profile of each installation is listed in `[Install*]`
+    // sections, these can also be split out to `installs.ini`
+    if let Ok(installs) = fs::read_to_string(root.join(FIREFOX_INSTALLS_INI)) {
+        sections.extend(parse_ini(&installs));
+    }
+    let install_defaults: Vec<PathBuf> = sections
+        .iter()
+        .filter(|(name, _)| name.starts_with("Install"))
+        .filter_map(|(_, entries)| entries.get("Default"))
+        .map(|path| root.join(path))
+        .collect();
+
+    sections
+        .iter()
+        .filter(|(name, _)| name.starts_with("Profile"))
+        .find_map(|(_, entries)| {
+            let path = entries.get("Path")?;
+            let is_relative =
+                entries.get("IsRelative").is_none_or(|v| v == "1");
+            let path = if is_relative {
+                root.join(path)
+            } else {
+                PathBuf::from(path)
+            };
+            if path != profile_dir {
+                return None;
+            }
+            // `Default=1` is only used by older versions that
+            // lack `[Install*]` sections
+            let is_default = if install_defaults.is_empty() {
+                entries.get("Default").is_some_and(|v| v == "1")
+            } else {
+                install_defaults.contains(&path)
+            };
+            Some(ProfileInfo {
+                name: entries.get("Name")?.to_owned(),
+                is_default,
+            })
+        })
+}
+
+/// The subset of Chromium's `Local State` file that we care about
+#[derive(Debug, Deserialize)]
+struct LocalState {
+    profile: Option<LocalStateProfiles>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LocalStateProfiles {
+    #[serde(default)]
+    info_cache: HashMap<String, LocalStateProfile>,
+    last_used: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LocalStateProfile {
+    name: String,
+}
+
+/// Look up a Chromium profile in the `Local State` file of the user data
+/// directory. The cookie database is located in either
+/// `<user data>/<profile>/Cookies` or `<user data>/<profile>/Network/Cookies`.
+pub fn chromium_profile_info(db_path: &Path) -> Option<ProfileInfo> {
+    let profile_dir = db_path.ancestors().skip(1).take(2).find(|dir| {
+        dir.parent()
+            .is_some_and(|p| p.join(CHROMIUM_LOCAL_STATE).is_file())
+    })?;
+    let user_data_dir = profile_dir.parent()?;
+    let profile_key = profile_dir.file_name()?.to_string_lossy().to_string();
+
+    let content =
+        fs::read_to_string(user_data_dir.join(CHROMIUM_LOCAL_STATE)).ok()?;
+    let profiles = serde_json::from_str::<LocalState>(&content).ok()?.profile?;
+    let profile = profiles.info_cache.get(&profile_key)?;
+    let last_used = profiles.last_used.unwrap_or("Default".to_string());
+
+    Some(ProfileInfo {
+        name: profile.name.to_owned(),
+        is_default: profile_key == last_used,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::profiles::{
+        chromium_profile_info, firefox_profile_info, ProfileInfo,
+    };
+
+    #[test]
+    fn test_firefox_profile_info() {
+        let root = std::env::temp_dir().join("cookiecutter_test_profiles_ini");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(
+            root.join("profiles.ini"),
+            "[Install4F96D1932A9F858E]\nDefault=x8k2a1.default-release\n\n\
+             [Profile1]\nName=Work\nIsRelative=1\nPath=abcd.work\n\n\
+             [Profile0]\nName=default-release\nIsRelative=1\n\
+             Path=x8k2a1.default-release\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            firefox_profile_info(&root.join("abcd.work")),
+            Some(ProfileInfo {
+                name: "Work".to_string(),
+                is_default: false
+            })
+        );
+        assert!(
+            firefox_profile_info(&root.join("x8k2a1.default-release"))
+                .unwrap()
+                .is_default
+        );
+        assert_eq!(firefox_profile_info(&root.join("missing")), None);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_chromium_profile_info() {
+        let root = std::env::temp_dir().join("cookiecutter_test_local_state");
+        std::fs::create_dir_all(root.join("Profile 1/Network")).unwrap();
+        std::fs::write(
+            root.join("Local State"),
+            r#"{"profile":{"last_used":"Default","info_cache":{
+                "Default":{"name":"Personal"},
+                "Profile 1":{"name":"Work"}}}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            chromium_profile_info(&root.join("Profile 1/Network/Cookies")),
+            Some(ProfileInfo {
+                name: "Work".to_string(),
+                is_default: false
+            })
+        );
+        assert!(
+            chromium_profile_info(&root.join("Default/Cookies"))
+                .unwrap()
+                .is_default
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,253 @@
+use std::path::{Path, PathBuf};
+
+use crate::config::{MAC_EPOCH_OFFSET, SAFARI_MAGIC};
+use crate::cookie::Cookie;
+use crate::error::CookieError;
+use crate::store::{Capabilities, CookieStore, Schema};
+
+/// Cookie flags in a Safari cookie record
+const FLAG_SECURE: u32 = 0x1;
+const FLAG_HTTP_ONLY: u32 = 0x4;
+
+/// Bounds checked reads from a `Cookies.binarycookies` file
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(
+        &self,
+        offset: usize,
+        len: usize,
+    ) -> Result<&'a [u8], CookieError> {
+        self.data
+            .get(offset..offset.saturating_add(len))
+            .ok_or_else(|| {
+                CookieError::Format(format!(
+                    "Read of {len} bytes at offset {offset} is out of bounds"
+                ))
+            })
+    }
+
+    fn u32_be(&self, offset: usize) -> Result<u32, CookieError> {
+        let bytes = self.bytes(offset, 4)?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn u32_le(&self, offset: usize) -> Result<u32, CookieError> {
+        let bytes = self.bytes(offset, 4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn f64_le(&self, offset: usize) -> Result<f64, CookieError> {
+        let bytes = self.bytes(offset, 8)?;
+        Ok(f64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// A NUL terminated string starting at `offset`
+    fn c_str(&self, offset: usize) -> Result<String, CookieError> {
+        let rest = self.data.get(offset..).ok_or_else(|| {
+            CookieError::Format(format!(
+                "String offset {offset} is out of bounds"
+            ))
+        })?;
+        let end = rest.iter().position(|b| *b == 0).unwrap_or(rest.len());
+        Ok(String::from_utf8_lossy(&rest[..end]).to_string())
+    }
+}
+
+/// Convert a Mac absolute time (seconds since 2001-01-01) into UNIX epoch
+/// time, 0 is kept as is for unset timestamps
+fn mac_to_unix_epoch(timestamp: f64) -> i64 {
+    if timestamp == 0.0 {
+        0
+    } else {
+        timestamp as i64 + MAC_EPOCH_OFFSET
+    }
+}
+
+/// Parse a single cookie record, all fields are little endian:
+///     size (4) || unknown (4) || flags (4) || unknown (4) ||
+///     domain offset (4) || name offset (4) || path offset (4) ||
+///     value offset (4) || comment (8) || expiry (f64) || creation (f64)
+/// The offsets point to NUL terminated strings within the record.
+fn parse_record(record: &Reader) -> Result<Cookie, CookieError> {
+    let flags = record.u32_le(8)?;
+    Ok(Cookie {
+        host: record.c_str(record.u32_le(16)? as usize)?,
+        name: record.c_str(record.u32_le(20)? as usize)?,
+        path: record.c_str(record.u32_le(24)? as usize)?,
+        value: record.c_str(record.u32_le(28)? as usize)?,
+        creation: mac_to_unix_epoch(record.f64_le(48)?),
+        expiry: mac_to_unix_epoch(record.f64_le(40)?),
+        // Safari does not store access times
+        last_access: 0,
+        http_only: flags & FLAG_HTTP_ONLY != 0,
+        secure: flags & FLAG_SECURE != 0,
+        // The `SameSite` attribute is not stored in a documented way
+        samesite: -1,
+        encrypted_value: vec![],
+        origin_attributes: None,
+        scheme_map: None,
+        is_partitioned_attribute_set: None,
+        priority: None,
+        source_scheme: None,
+        source_port: None,
+        is_persistent: None,
+        has_expires: None,
+        top_frame_site_key: None,
+        last_update: None,
+        container: None,
+        from_session_store: false,
+    })
+}
+
+/// Parse the contents of a Safari `Cookies.binarycookies` file:
+///     "cook" || page count (u32 BE) || page sizes (u32 BE each) ||
+///     pages || checksum and metadata
+/// Each page consists of:
+///     0x00000100 || cookie count (u32 LE) || record offsets (u32 LE each)
+///     || 0x00000000 || records
+pub fn parse_binary_cookies(data: &[u8]) -> Result<Vec<Cookie>, CookieError> {
+    let file = Reader { data };
+    if file.bytes(0, SAFARI_MAGIC.len())? != SAFARI_MAGIC {
+        return Err(CookieError::Format("Missing 'cook' header".to_string()));
+    }
+    let page_count = file.u32_be(4)? as usize;
+
+    let mut cookies = vec![];
+    let mut page_offset = 8 + 4 * page_count;
+    for i in 0..page_count {
+        let page_size = file.u32_be(8 + 4 * i)? as usize;
+        let page = Reader {
+            data: file.bytes(page_offset, page_size)?,
+        };
+        let cookie_count = page.u32_le(4)? as usize;
+        for j in 0..cookie_count {
+            let record_offset = page.u32_le(8 + 4 * j)? as usize;
+            let record_size = page.u32_le(record_offset)? as usize;
+            let record = Reader {
+                data: page.bytes(record_offset, record_size)?,
+            };
+            cookies.push(parse_record(&record)?);
+        }
+        page_offset += page_size;
+    }
+    Ok(cookies)
+}
+
+/// Load all cookies from a Safari `Cookies.binarycookies` file
+pub fn load_binary_cookies(path: &Path) -> Result<Vec<Cookie>, CookieError> {
+    parse_binary_cookies(&std::fs::read(path)?)
+}
+
+/// A Safari `Cookies.binarycookies` file, cookies can only be read
+pub struct SafariStore {
+    path: PathBuf,
+}
+
+impl SafariStore {
+    pub fn new(path: &Path) -> Self {
+        SafariStore {
+            path: path.to_path_buf(),
+        }
+    }
+}
+
+impl CookieStore for SafariStore {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            writable: false,
+            encrypted: false,
+        }
+    }
+
+    fn schema(&self) -> Result<Schema, CookieError> {
+        Ok(Schema {
+            format: "binarycookies".to_string(),
+            version: None,
+            columns: vec![],
+        })
+    }
+
+    fn load(&self) -> Result<Vec<Cookie>, CookieError> {
+        load_binary_cookies(&self.path)
+    }
+
+    fn insert(&self, _: &[Cookie]) -> Result<(), CookieError> {
+        Err(CookieError::ReadOnly("Safari"))
+    }
+
+    fn update(&self, _: &[Cookie]) -> Result<(), CookieError> {
+        Err(CookieError::ReadOnly("Safari"))
+    }
+
+    fn delete(&self, _: &[Cookie]) -> Result<(), CookieError> {
+        Err(CookieError::ReadOnly("Safari"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::safari::parse_binary_cookies;
+
+    /// Encode a cookie record like Safari does
+    fn record(flags: u32, strings: [&str; 4], expiry: f64) -> Vec<u8> {
+        let mut body = vec![];
+        let mut offsets = vec![];
+        for s in strings {
+            offsets.push(56 + body.len() as u32);
+            body.extend_from_slice(s.as_bytes());
+            body.push(0);
+        }
+        let size = 56 + body.len() as u32;
+        let mut record = vec![];
+        for field in [size, 0, flags, 0] {
+            record.extend_from_slice(&field.to_le_bytes());
+        }
+        for offset in offsets {
+            record.extend_from_slice(&offset.to_le_bytes());
+        }
+        record.extend_from_slice(&[0; 8]);
+        record.extend_from_slice(&expiry.to_le_bytes());
+        record.extend_from_slice(&0f64.to_le_bytes());
+        record.extend(body);
+        record
+    }
+
+    #[test]
+    fn test_parse_binary_cookies() {
+        let records = [
+            record(5, [".example.com", "sid", "/", "abc"], 700_000_000.0),
+            record(0, ["site.org", "pref", "/docs", "1"], 0.0),
+        ];
+        let mut page = vec![0, 0, 1, 0];
+        page.extend_from_slice(&(records.len() as u32).to_le_bytes());
+        let mut offset = 8 + 4 * records.len() as u32 + 4;
+        for r in records.iter() {
+            page.extend_from_slice(&offset.to_le_bytes());
+            offset += r.len() as u32;
+        }
+        page.extend_from_slice(&[0; 4]);
+        page.extend(records.concat());
+
+        let mut data = b"cook".to_vec();
+        data.extend_from_slice(&1u32.to_be_bytes());
+        data.extend_from_slice(&(page.len() as u32).to_be_bytes());
+        data.extend(page);
+        data.extend_from_slice(&[0; 8]);
+
+        let cookies = parse_binary_cookies(&data).unwrap();
+        assert_eq!(cookies.len(), 2);
+        assert_eq!(cookies[0].host, ".example.com");
+        assert_eq!(cookies[0].value, "abc");
+        assert!(cookies[0].secure && cookies[0].http_only);
+        assert_eq!(cookies[0].expiry, 700_000_000 + 978_307_200);
+        assert_eq!(cookies[1].path, "/docs");
+        assert!(!cookies[1].secure);
+        assert_eq!(cookies[1].expiry, 0);
+
+        assert!(parse_binary_cookies(b"SQLite format 3").is_err());
+        assert!(parse_binary_cookies(&data[..40]).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::config::{FIREFOX_SESSION_FILES, MOZLZ4_MAGIC};
+use crate::cookie::{Cookie, OriginAttributes};
+
+/// The subset of a Firefox session file that we care about
+#[derive(Debug, Deserialize)]
+struct SessionStore {
+    #[serde(default)]
+    cookies: Vec<SessionCookie>,
+}
+
+/// A cookie as serialised by Firefox's `SessionCookies` module,
+/// attributes at their default values are left out
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionCookie {
+    host: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    value: String,
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    secure: bool,
+    #[serde(default)]
+    httponly: bool,
+    /// Expiry in UNIX epoch seconds, missing for session cookies
+    #[serde(default)]
+    expiry: i64,
+    #[serde(default)]
+    same_site: i32,
+    scheme_map: Option<i64>,
+    #[serde(default)]
+    origin_attributes: SessionOriginAttributes,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionOriginAttributes {
+    #[serde(default)]
+    user_context_id: u32,
+    #[serde(default)]
+    private_browsing_id: u32,
+    #[serde(default)]
+    first_party_domain: String,
+    #[serde(default)]
+    partition_key: String,
+}
+
+impl From<SessionCookie> for Cookie {
+    fn from(c: SessionCookie) -> Self {
+        let origin_attributes = OriginAttributes {
+            user_context_id: c.origin_attributes.user_context_id,
+            private_browsing_id: c.origin_attributes.private_browsing_id,
+            first_party_domain: c.origin_attributes.first_party_domain,
+            partition_key: c.origin_attributes.partition_key,
+        };
+        Cookie {
+            host: c.host,
+            name: c.name,
+            value: c.value,
+            path: c.path,
+            // Creation and access times are not part of the session file
+            creation: 0,
+            expiry: c.expiry,
+            last_access: 0,
+            http_only: c.httponly,
+            secure: c.secure,
+            samesite: c.same_site,
+            encrypted_value: vec![],
+            origin_attributes: Some(origin_attributes.suffix()),
+            scheme_map: c.scheme_map,
+            is_partitioned_attribute_set: None,
+            priority: None,
+            source_scheme: None,
+            source_port: None,
+            is_persistent: None,
+            has_expires: None,
+            top_frame_site_key: None,
+            last_update: None,
+            container: None,
+            from_session_store: true,
+        }
+    }
+}
+
+/// Decompress a mozLz4 file, i.e. a LZ4 block prefixed with a magic
+/// number and the size of the decompressed data:
+///     "mozLz40\0" || size (u32, little endian) || LZ4 block
+pub fn read_mozlz4(path: &Path) -> Result<Vec<u8>, io::Error> {
+    let content = std::fs::read(path)?;
+    let block = content.strip_prefix(MOZLZ4_MAGIC).ok_or(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "Missing mozLz4 header",
+    ))?;
+    lz4_flex::block::decompress_size_prepended(block)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Load the session cookies of a Firefox profile from the most recent
+/// session file, empty if the profile has no readable session file
+pub fn load_session_cookies(profile_dir: &Path) -> Vec<Cookie> {
+    FIREFOX_SESSION_FILES
+        .iter()
+        .map(|f| profile_dir.join(f))
+        .filter(|p| p.is_file())
+        .find_map(|p| {
+            let json = read_mozlz4(&p).ok()?;
+            serde_json::from_slice::<SessionStore>(&json).ok()
+        })
+        .map(|s| s.cookies.into_iter().map(Cookie::from).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::session_store::load_session_cookies;
+
+    #[test]
+    fn test_load_session_cookies() {
+        let profile_dir =
+            std::env::temp_dir().join("cookiecutter_test_session_store");
+        std::fs::create_dir_all(profile_dir.join("sessionstore-backups"))
+            .unwrap();
+        let json = r#"{"version":["sessionrestore",1],"windows":[],
+            "cookies":[
+                {"host":".example.com","value":"abc","path":"/",
+                 "name":"sid","secure":true,"httponly":true,
+                 "originAttributes":{"userContextId":2,
+                    "firstPartyDomain":"","partitionKey":""}},
+                {"host":"site.org","value":"1","name":"pref",
+                 "sameSite":2,"originAttributes":{}}
+            ]}"#;
+        let compressed =
+            lz4_flex::block::compress_prepend_size(json.as_bytes());
+        std::fs::write(
+            profile_dir.join("sessionstore-backups/recovery.jsonlz4"),
+            [b"mozLz40\0".as_ref(), &compressed].concat(),
+        )
+        .unwrap();
+
+        let cookies = load_session_cookies(&profile_dir);
+        assert_eq!(cookies.len(), 2);
+        assert_eq!(cookies[0].name, "sid");
+        assert_eq!(cookies[0].origin().user_context_id, 2);
+        assert!(cookies[0].http_only && cookies[0].from_session_store);
+        assert_eq!(cookies[1].origin_attributes.as_deref(), Some(""));
+        assert_eq!(cookies[1].samesite, 2);
+
+        std::fs::remove_dir_all(&profile_dir).unwrap();
+    }
+}
@@ -0,0 +1,265 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{SecondsFormat, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::SNAPSHOT_DIR;
+use crate::cookie::Cookie;
+use crate::cookie_db::CookieDB;
+use crate::diff::{diff_cookies, CookieChange};
+use crate::filter::CookieFilter;
+
+/// The cookies of a set of profiles at a point in time, stored as
+/// `$XDG_DATA_HOME/cookiecutter/snapshots/<name>.json`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// When the snapshot was taken, in UNIX epoch time
+    pub created: i64,
+    pub profiles: Vec<ProfileSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProfileSnapshot {
+    /// The path of the cookie database, profiles
+    /// are matched on this between snapshots
+    pub path: PathBuf,
+    /// The display name of the profile when the snapshot was taken
+    pub name: String,
+    pub cookies: Vec<Cookie>,
+}
+
+impl Snapshot {
+    /// A snapshot of the loaded cookies of each database
+    pub fn new(cookie_dbs: &[CookieDB]) -> Self {
+        Snapshot {
+            created: Utc::now().timestamp(),
+            profiles: cookie_dbs
+                .iter()
+                .map(|cdb| ProfileSnapshot {
+                    path: cdb.path.clone(),
+                    name: cdb.display_name(),
+                    cookies: cdb.cookies.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn created_str(&self) -> String {
+        Utc.timestamp_opt(self.created, 0)
+            .single()
+            .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true))
+            .unwrap_or_default()
+    }
+
+    pub fn cookie_count(&self) -> usize {
+        self.profiles.iter().map(|p| p.cookies.len()).sum()
+    }
+
+    /// Only keep the profiles with one of the given database paths
+    pub fn retain_profiles(&mut self, paths: &[&Path]) {
+        self.profiles.retain(|p| paths.contains(&p.path.as_path()));
+    }
+
+    /// The directory that snapshots are saved in
+    fn dir() -> Result<PathBuf, String> {
+        let data_home = match std::env::var("XDG_DATA_HOME") {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => std::env::var("HOME")
+                .map(|home| PathBuf::from(home).join(".local/share"))
+                .map_err(|_| "Neither $XDG_DATA_HOME nor $HOME is set")?,
+        };
+        Ok(data_home.join(SNAPSHOT_DIR))
+    }
+
+    /// The file of a snapshot, names are limited to characters
+    /// that are safe to use in a filename
+    fn path(dir: &Path, name: &str) -> Result<PathBuf, String> {
+        let is_valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c));
+        if !is_valid {
+            return Err(format!(
+                "Invalid snapshot name '{name}', use letters, digits, \
+                 '.', '_' and '-'"
+            ));
+        }
+        Ok(dir.join(format!("{name}.json")))
+    }
+
+    pub fn save(&self, name: &str) -> Result<(), String> {
+        self.save_in(&Self::dir()?, name)
+    }
+
+    pub fn load(name: &str) -> Result<Self, String> {
+        Self::load_from(&Self::dir()?, name)
+    }
+
+    /// The names of all saved snapshots, sorted by name
+    pub fn list() -> Result<Vec<String>, String> {
+        Self::list_in(&Self::dir()?)
+    }
+
+    /// Snapshots contain decrypted cookie values,
+    /// so the file is only readable by the owner
+    fn save_in(&self, dir: &Path, name: &str) -> Result<(), String> {
+        let path = Self::path(dir, name)?;
+        let json = serde_json::to_vec(self).map_err(|e| e.to_string())?;
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        std::fs::create_dir_all(dir)
+            .and_then(|_| options.open(&path))
+            .and_then(|mut f| f.write_all(&json))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn load_from(dir: &Path, name: &str) -> Result<Self, String> {
+        let path = Self::path(dir, name)?;
+        if !path.is_file() {
+            return Err(format!("No snapshot named '{name}'"));
+        }
+        let content = std::fs::read(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        serde_json::from_slice(&content)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn list_in(dir: &Path) -> Result<Vec<String>, String> {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(vec![]);
+            }
+            Err(e) => return Err(format!("{}: {}", dir.display(), e)),
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name().to_string_lossy().to_string();
+                name.strip_suffix(".json").map(|n| n.to_string())
+            })
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+}
+
+/// The changes to each profile from one snapshot to another, for the
+/// cookies that match the filter. Profiles are matched on the path of
+/// their database, a profile that only exists in one of the snapshots
+/// has all of its cookies added or removed. Profiles without any
+/// changes are left out.
+pub fn diff_snapshots(
+    old: &Snapshot,
+    new: &Snapshot,
+    filter: &CookieFilter,
+) -> Vec<(String, Vec<CookieChange>)> {
+    let matching = |p: Option<&ProfileSnapshot>| -> Vec<Cookie> {
+        p.map(|p| p.cookies.iter().filter(|c| filter.matches(c)))
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect()
+    };
+    let removed_profiles = old
+        .profiles
+        .iter()
+        .filter(|o| !new.profiles.iter().any(|n| n.path == o.path));
+
+    let mut profile_changes = vec![];
+    for profile in new.profiles.iter().chain(removed_profiles) {
+        let old_profile = old.profiles.iter().find(|p| p.path == profile.path);
+        let new_profile = new.profiles.iter().find(|p| p.path == profile.path);
+        let changes =
+            diff_cookies(&matching(old_profile), &matching(new_profile));
+        if !changes.is_empty() {
+            profile_changes.push((profile.name.to_owned(), changes));
+        }
+    }
+    profile_changes
+}
+
+/// Print the changes between two snapshots grouped by profile and domain
+pub fn print_diff(old: &Snapshot, new: &Snapshot, filter: &CookieFilter) {
+    let profile_changes = diff_snapshots(old, new, filter);
+    if profile_changes.is_empty() {
+        println!("No changes");
+    }
+    for (name, changes) in profile_changes {
+        println!("{name}");
+        let mut host = None;
+        for change in changes.iter() {
+            let c = change.cookie();
+            // Changes are ordered by host
+            if host != Some(&c.host) {
+                host = Some(&c.host);
+                println!("  {}", c.host);
+            }
+            println!("    {} {}{}", change.sign(), c.name, change.details());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::cookie::tests::cookie;
+    use crate::cookie::Cookie;
+    use crate::filter::CookieFilter;
+    use crate::snapshot::{diff_snapshots, ProfileSnapshot, Snapshot};
+
+    fn profile(path: &str, cookies: Vec<Cookie>) -> ProfileSnapshot {
+        ProfileSnapshot {
+            path: PathBuf::from(path),
+            name: path.to_string(),
+            cookies,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_diff() {
+        let dir = std::env::temp_dir().join("cookiecutter_test_snapshots");
+        let _ = std::fs::remove_dir_all(&dir);
+        let before = Snapshot {
+            created: 1_700_000_000,
+            profiles: vec![
+                profile("a", vec![cookie("example.com", "sid", "1")]),
+                profile("b", vec![cookie("site.org", "pref", "x")]),
+            ],
+        };
+        before.save_in(&dir, "before").unwrap();
+        assert!(before.save_in(&dir, "../escape").is_err());
+        assert_eq!(Snapshot::list_in(&dir).unwrap(), ["before"]);
+
+        let before = Snapshot::load_from(&dir, "before").unwrap();
+        let after = Snapshot {
+            created: 1_700_000_100,
+            profiles: vec![
+                profile("a", vec![cookie("example.com", "sid", "2")]),
+                profile("c", vec![cookie("new.net", "id", "1")]),
+            ],
+        };
+        let changes: Vec<(String, Vec<&str>)> =
+            diff_snapshots(&before, &after, &CookieFilter::default())
+                .iter()
+                .map(|(name, changes)| {
+                    (name.clone(), changes.iter().map(|c| c.kind()).collect())
+                })
+                .collect();
+        assert_eq!(
+            changes,
+            [
+                ("a".to_string(), vec!["changed"]),
+                ("c".to_string(), vec!["added"]),
+                ("b".to_string(), vec!["removed"]),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+use std::cmp::Ordering;
+
+use crate::cookie::Cookie;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortField {
+    Host,
+    Name,
+    Expiry,
+    Creation,
+    LastAccess,
+    /// The size of the value
+    Size,
+}
+
+/// A field to sort cookies by, e.g. `expiry:desc`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+impl SortKey {
+    /// Parse a comma separated list of fields, each optionally followed
+    /// by `:asc` (the default) or `:desc`
+    pub fn parse_list(s: &str) -> Result<Vec<SortKey>, String> {
+        s.split(',')
+            .filter(|key| !key.is_empty())
+            .map(SortKey::parse)
+            .collect()
+    }
+
+    fn parse(s: &str) -> Result<SortKey, String> {
+        let (field, order) = s.split_once(':').unwrap_or((s, "asc"));
+        let field = match field.to_lowercase().as_str() {
+            "host" => SortField::Host,
+            "name" => SortField::Name,
+            "expiry" => SortField::Expiry,
+            "creation" => SortField::Creation,
+            "last_access" => SortField::LastAccess,
+            "size" => SortField::Size,
+            _ => {
+                return Err(format!(
+                    "Invalid sort field '{field}', expected one of: \
+                     host, name, expiry, creation, last_access, size"
+                ))
+            }
+        };
+        let descending = match order.to_lowercase().as_str() {
+            "asc" => false,
+            "desc" => true,
+            _ => {
+                return Err(format!(
+                    "Invalid sort order '{order}', expected asc or desc"
+                ))
+            }
+        };
+        Ok(SortKey { field, descending })
+    }
+
+    pub fn cmp(&self, a: &Cookie, b: &Cookie) -> Ordering {
+        let ordering = match self.field {
+            SortField::Host => a.host.cmp(&b.host),
+            SortField::Name => a.name.cmp(&b.name),
+            SortField::Expiry => a.expiry.cmp(&b.expiry),
+            SortField::Creation => a.creation.cmp(&b.creation),
+            SortField::LastAccess => a.last_access.cmp(&b.last_access),
+            SortField::Size => a.value_size().cmp(&b.value_size()),
+        };
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+/// Compare two cookies by each key in turn
+pub fn cmp_cookies(keys: &[SortKey], a: &Cookie, b: &Cookie) -> Ordering {
+    keys.iter()
+        .map(|key| key.cmp(a, b))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cookie::tests::cookie;
+    use crate::cookie::Cookie;
+    use crate::sort::{cmp_cookies, SortField, SortKey};
+
+    #[test]
+    fn test_sort_cookies() {
+        let keys = SortKey::parse_list("host,expiry:desc").unwrap();
+        assert_eq!(
+            keys[1],
+            SortKey {
+                field: SortField::Expiry,
+                descending: true
+            }
+        );
+        assert!(SortKey::parse_list("path").is_err());
+        assert!(SortKey::parse_list("name:up").is_err());
+
+        let mut cookies = [
+            Cookie {
+                expiry: 10,
+                ..cookie("b.com", "x", "a")
+            },
+            Cookie {
+                expiry: 10,
+                ..cookie("a.com", "y", "abc")
+            },
+            Cookie {
+                expiry: 20,
+                ..cookie("b.com", "z", "ab")
+            },
+        ];
+        cookies.sort_by(|a, b| cmp_cookies(&keys, a, b));
+        let names: Vec<&str> =
+            cookies.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["y", "z", "x"]);
+
+        let keys = SortKey::parse_list("size").unwrap();
+        cookies.sort_by(|a, b| cmp_cookies(&keys, a, b));
+        let names: Vec<&str> =
+            cookies.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["x", "z", "y"]);
+    }
+}
@@ -0,0 +1,277 @@
+use clap::ValueEnum;
+use rusqlite::limits::Limit;
+use rusqlite::types::Value;
+use rusqlite::{Connection, OpenFlags};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::Value as JsonValue;
+
+use crate::cookie_db::CookieDB;
+use crate::store::{sql_column, sql_fields};
+use crate::util::percent_encode;
+
+#[derive(Debug, Clone, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+/// The columns and rows of a query result
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// Quote a string as an SQL literal
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Attach each SQLite database read-only as `db<N>` to an in-memory
+/// database and create a temporary `cookies` view over all of them with
+/// the columns `browser`, `profile` and `db_path` followed by the cookie
+/// fields. Databases that can not be attached are skipped with a warning.
+/// No further databases can be attached to the returned connection.
+pub fn open_unified(cookie_dbs: &[CookieDB]) -> Result<Connection, String> {
+    let conn = Connection::open_in_memory_with_flags(
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+            | OpenFlags::SQLITE_OPEN_CREATE
+            | OpenFlags::SQLITE_OPEN_URI,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let mut selects = vec![];
+    for (i, cdb) in cookie_dbs.iter().enumerate() {
+        let schema = format!("db{i}");
+        let path =
+            std::fs::canonicalize(&cdb.path).unwrap_or(cdb.path.to_owned());
+        // Paths are encoded so that '?' and '#' are not
+        // interpreted as parts of the URI
+        let uri =
+            format!("file:{}?mode=ro", percent_encode(&path.to_string_lossy()));
+        let select = cdb.store().map_err(|e| e.to_string()).and_then(|s| {
+            conn.execute(&format!("ATTACH DATABASE ?1 AS {schema}"), [&uri])
+                .map_err(|e| e.to_string())?;
+            s.sql_select(&conn, &schema).map_err(|e| e.to_string())
+        });
+        match select {
+            Ok(Some(select)) => {
+                let profile = cdb.profile.as_ref().map(|p| p.name.as_str());
+                selects.push(format!(
+                    "SELECT {} AS browser, {} AS profile, {} AS db_path, * \
+                     FROM ({select})",
+                    quote(&cdb.browser),
+                    profile.map_or("NULL".to_string(), quote),
+                    quote(&path.to_string_lossy())
+                ));
+            }
+            // Not a SQLite database
+            Ok(None) => {}
+            Err(err) => eprintln!("WARN: {}: {err}", cdb.path_short()),
+        }
+    }
+    // An empty view still has all of the columns
+    if selects.is_empty() {
+        let columns: Vec<String> = ["browser", "profile", "db_path"]
+            .into_iter()
+            .map(String::from)
+            .chain(sql_fields().into_iter().map(sql_column))
+            .map(|c| format!("NULL AS {c}"))
+            .collect();
+        selects.push(format!("SELECT {} WHERE 0", columns.join(", ")));
+    }
+    conn.execute_batch(&format!(
+        "CREATE TEMP VIEW cookies AS {};\nPRAGMA query_only = 1;",
+        selects.join("\nUNION ALL ")
+    ))
+    .map_err(|e| e.to_string())?;
+    // ATTACH counts as read-only, but creates the file it is given
+    conn.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0)
+        .map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
+/// Run a single read-only statement
+pub fn query(conn: &Connection, sql: &str) -> Result<QueryResult, String> {
+    let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
+    if !stmt.readonly() {
+        return Err("Only read-only queries are allowed".to_string());
+    }
+    let columns: Vec<String> =
+        stmt.column_names().into_iter().map(String::from).collect();
+    let rows = stmt
+        .query_map([], |row| {
+            (0..columns.len()).map(|i| row.get::<_, Value>(i)).collect()
+        })
+        .and_then(|rows| rows.collect::<Result<Vec<Vec<Value>>, _>>())
+        .map_err(|e| e.to_string())?;
+    Ok(QueryResult { columns, rows })
+}
+
+fn value_str(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Integer(i) => i.to_string(),
+        Value::Real(r) => r.to_string(),
+        Value::Text(t) => t.to_owned(),
+        Value::Blob(b) => b.iter().map(|byte| format!("{byte:02x}")).collect(),
+    }
+}
+
+fn value_json(value: &Value) -> JsonValue {
+    match value {
+        Value::Null => JsonValue::Null,
+        Value::Integer(i) => JsonValue::from(*i),
+        Value::Real(r) => JsonValue::from(*r),
+        Value::Text(_) | Value::Blob(_) => JsonValue::from(value_str(value)),
+    }
+}
+
+/// A row serialised as an object with keys in column order
+struct JsonRow<'a> {
+    columns: &'a [String],
+    values: &'a [Value],
+}
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for (column, value) in self.columns.iter().zip(self.values.iter()) {
+            map.serialize_entry(column, &value_json(value))?;
+        }
+        map.end()
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl QueryResult {
+    pub fn to_table(&self) -> String {
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(value_str).collect())
+            .collect();
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                cells
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([c.chars().count()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let line = |values: &[String]| -> String {
+            let padded: Vec<String> = values
+                .iter()
+                .zip(widths.iter())
+                .map(|(v, w)| format!("{v:<w$}"))
+                .collect();
+            padded.join("  ").trim_end().to_string() + "\n"
+        };
+        let mut out = line(&self.columns);
+        let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+        out += &line(&rule);
+        for row in cells.iter() {
+            out += &line(row);
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let rows: Vec<JsonRow> = self
+            .rows
+            .iter()
+            .map(|row| JsonRow {
+                columns: &self.columns,
+                values: row,
+            })
+            .collect();
+        serde_json::to_string_pretty(&rows).unwrap_or_default() + "\n"
+    }
+
+    pub fn to_csv(&self) -> String {
+        let line = |values: Vec<String>| -> String {
+            let fields: Vec<String> =
+                values.iter().map(|v| csv_field(v)).collect();
+            fields.join(",") + "\r\n"
+        };
+        let mut out = line(self.columns.clone());
+        for row in self.rows.iter() {
+            out += &line(row.iter().map(value_str).collect());
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::cookie_db::CookieDB;
+    use crate::sql::{open_unified, query};
+    use crate::util::DbType;
+
+    #[test]
+    fn test_sql_cookies_view() {
+        let path = std::env::temp_dir().join("cookiecutter_test_sql ?#.sqlite");
+        let _ = std::fs::remove_file(&path);
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE moz_cookies (id INTEGER PRIMARY KEY, \
+             originAttributes TEXT NOT NULL DEFAULT '', name TEXT, \
+             value TEXT, host TEXT, path TEXT, expiry INTEGER, \
+             lastAccessed INTEGER, creationTime INTEGER, isSecure INTEGER, \
+             isHttpOnly INTEGER, sameSite INTEGER);
+             INSERT INTO moz_cookies VALUES (1, '', 'sid', 'a,\"b\"', \
+             '.example.com', '/', 1900000000, 1700000000000000, \
+             1600000000000000, 1, 0, 2);",
+        )
+        .unwrap();
+        drop(conn);
+
+        let cdb = CookieDB::new(path.clone(), DbType::Firefox, "Firefox");
+        let safari = CookieDB::new(
+            Path::new("Cookies.binarycookies").to_path_buf(),
+            DbType::Safari,
+            "Safari",
+        );
+        let conn = open_unified(&[cdb, safari]).unwrap();
+        let result = query(
+            &conn,
+            "SELECT browser, name, value, creation, expiry, last_access, \
+             priority FROM cookies",
+        )
+        .unwrap();
+        assert_eq!(
+            result.to_csv(),
+            "browser,name,value,creation,expiry,last_access,priority\r\n\
+             Firefox,sid,\"a,\"\"b\"\"\",1600000000,1900000000,1700000000,\r\n"
+        );
+        assert!(query(&conn, "DELETE FROM db0.moz_cookies").is_err());
+        assert!(query(&conn, "CREATE TABLE t (a)").is_err());
+        let attached = path.with_file_name("cookiecutter_test_sql_attach.db");
+        let attach = format!("ATTACH DATABASE '{}' AS x", attached.display());
+        assert!(query(&conn, &attach).is_err());
+        assert!(!attached.exists());
+
+        let empty = open_unified(&[]).unwrap();
+        let result = query(&empty, "SELECT * FROM cookies").unwrap();
+        assert!(result.rows.is_empty());
+        assert_eq!(result.columns[3], "host");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
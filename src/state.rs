@@ -2,6 +2,7 @@ use tui::widgets::ListState;
 
 use crate::config::NO_SELECTION;
 use crate::cookie_db::CookieDB;
+use crate::sort::{SortField, SortKey};
 
 pub struct StatefulList<T> {
     pub status: ListState,
@@ -29,6 +30,27 @@ impl<T> StatefulList<T> {
         };
         self.status.select(Some(i));
     }
+    /// Replace the items of the list, the selected item stays selected
+    /// if it still exists. Otherwise the selected index is kept, as long
+    /// as it is within bounds of the new items.
+    pub fn replace_items(&mut self, items: Vec<T>)
+    where
+        T: PartialEq,
+    {
+        let selected = self
+            .status
+            .selected()
+            .and_then(|i| self.items.get(i))
+            .and_then(|item| items.iter().position(|i| i == item));
+        let idx = selected.or_else(|| {
+            self.status
+                .selected()
+                .filter(|_| !items.is_empty())
+                .map(|i| i.min(items.len() - 1))
+        });
+        self.items = items;
+        self.status.select(idx);
+    }
     pub fn previous(&mut self) {
         let i = match self.status.selected() {
             Some(i) => {
@@ -51,6 +73,76 @@ pub enum Selection {
     Cookies,
 }
 
+/// The order of the domains split, cycled with 's'
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DomainOrder {
+    Name,
+    /// Most cookies first
+    Count,
+    /// Most recently accessed cookie first
+    LastAccess,
+}
+
+impl DomainOrder {
+    pub fn next(self) -> Self {
+        match self {
+            DomainOrder::Name => DomainOrder::Count,
+            DomainOrder::Count => DomainOrder::LastAccess,
+            DomainOrder::LastAccess => DomainOrder::Name,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DomainOrder::Name => "name",
+            DomainOrder::Count => "count",
+            DomainOrder::LastAccess => "recent",
+        }
+    }
+}
+
+/// The order of the cookies split, cycled with 's'
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CookieOrder {
+    /// The order of the database
+    Database,
+    Name,
+    /// Most recently accessed first
+    LastAccess,
+    /// Soonest to expire first
+    Expiry,
+}
+
+impl CookieOrder {
+    pub fn next(self) -> Self {
+        match self {
+            CookieOrder::Database => CookieOrder::Name,
+            CookieOrder::Name => CookieOrder::LastAccess,
+            CookieOrder::LastAccess => CookieOrder::Expiry,
+            CookieOrder::Expiry => CookieOrder::Database,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CookieOrder::Database => "database",
+            CookieOrder::Name => "name",
+            CookieOrder::LastAccess => "recent",
+            CookieOrder::Expiry => "expiry",
+        }
+    }
+
+    pub fn sort_keys(self) -> Vec<SortKey> {
+        let (field, descending) = match self {
+            CookieOrder::Database => return vec![],
+            CookieOrder::Name => (SortField::Name, false),
+            CookieOrder::LastAccess => (SortField::LastAccess, true),
+            CookieOrder::Expiry => (SortField::Expiry, false),
+        };
+        vec![SortKey { field, descending }]
+    }
+}
+
 /// The main struct which holds the global state of the TUI
 pub struct State {
     /// The currently selected element
@@ -73,15 +165,27 @@ pub struct State {
     pub current_domains: StatefulList<String>,
     pub current_cookies: StatefulList<String>,
     pub current_fields: StatefulList<String>,
+
+    pub domain_order: DomainOrder,
+    pub cookie_order: CookieOrder,
+
+    /// The selected profile, domain and cookie that the lists above
+    /// were last filled for, the lists are only refilled on a change
+    pub shown: Option<ListSelection>,
+    /// Set when the selected database was modified, e.g. by a deletion
+    pub stale: bool,
 }
 
+/// The selected index in the profiles, domains and cookies lists
+pub type ListSelection = (Option<usize>, Option<usize>, Option<usize>);
+
 impl State {
     /// Create a TUI state object from a vector of cookie databases
     pub fn new(cookie_dbs: &Vec<CookieDB>) -> State {
         // The profiles list will never change after launch
         let profiles = StatefulList {
             status: ListState::default(),
-            items: cookie_dbs.iter().map(|c| c.path_short()).collect(),
+            items: cookie_dbs.iter().map(|c| c.display_name()).collect(),
         };
         State {
             selection: Selection::Profiles,
@@ -93,22 +197,20 @@ impl State {
             current_domains: StatefulList::default(),
             current_cookies: StatefulList::default(),
             current_fields: StatefulList::default(),
+            domain_order: DomainOrder::Name,
+            cookie_order: CookieOrder::Database,
+            shown: None,
+            stale: false,
         }
     }
 
-    /// The currently selected profile
-    pub fn selected_profile(&self) -> Option<String> {
-        if let Some(selected_idx) = self.profiles.status.selected() {
-            // Convert to String to dodge BC
-            let s = self
-                .profiles
-                .items
-                .get(selected_idx)
-                .expect("No profile found for `selected()` index");
-            Some(s.to_owned())
-        } else {
-            None
-        }
+    /// The selected index in each of the lists
+    pub fn list_selection(&self) -> ListSelection {
+        (
+            self.profiles.status.selected(),
+            self.current_domains.status.selected(),
+            self.current_cookies.status.selected(),
+        )
     }
 
     /// The currently selected domain (if any)
@@ -125,19 +227,28 @@ impl State {
             None
         }
     }
+}
 
-    /// The currently selected cookie (if any)
-    pub fn selected_cookie(&self) -> Option<String> {
-        if let Some(selected_idx) = self.current_cookies.status.selected() {
-            // Convert to String to dodge BC
-            let c = self
-                .current_cookies
-                .items
-                .get(selected_idx)
-                .expect("No cookie found for `selected()` index");
-            Some(c.to_owned())
-        } else {
-            None
-        }
+#[cfg(test)]
+mod tests {
+    use crate::state::StatefulList;
+
+    #[test]
+    fn test_replace_items() {
+        let mut list = StatefulList::default();
+        list.items = vec!["a", "b", "c"];
+        list.status.select(Some(1));
+
+        // The selected item moved
+        list.replace_items(vec!["0", "a", "b", "c"]);
+        assert_eq!(list.status.selected(), Some(2));
+
+        // The selected item was removed
+        list.replace_items(vec!["0", "a", "c"]);
+        assert_eq!(list.status.selected(), Some(2));
+        list.replace_items(vec!["0"]);
+        assert_eq!(list.status.selected(), Some(0));
+        list.replace_items(vec![]);
+        assert_eq!(list.status.selected(), None);
     }
 }
@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::Utc;
+
+use crate::cookie::Cookie;
+use crate::cookie_db::CookieDB;
+use crate::util::registrable_domain;
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Upper bounds for the distribution of the remaining
+/// lifetime of persistent cookies
+const EXPIRY_HORIZONS: &[(&str, i64)] = &[
+    ("< 1 day", DAY),
+    ("< 1 week", 7 * DAY),
+    ("< 1 month", 30 * DAY),
+    ("< 1 year", 365 * DAY),
+];
+
+/// Aggregate counts over a set of cookies
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub cookies: usize,
+    /// Cookies that expire at the end of the browser session
+    pub session: usize,
+    /// Persistent cookies that have expired but not been removed yet
+    pub expired: usize,
+    /// Persistent cookies per horizon in `EXPIRY_HORIZONS`,
+    /// followed by cookies that expire later
+    pub expiry: [usize; EXPIRY_HORIZONS.len() + 1],
+    pub samesite: BTreeMap<String, usize>,
+    pub secure: usize,
+    pub http_only: usize,
+    /// Cookies and bytes of values per registrable domain
+    pub domains: HashMap<String, (usize, usize)>,
+}
+
+fn is_session(c: &Cookie) -> bool {
+    c.expiry == 0 || c.is_persistent == Some(false)
+}
+
+impl Stats {
+    pub fn new<'a>(cookies: impl Iterator<Item = &'a Cookie>) -> Self {
+        let now = Utc::now().timestamp();
+        let mut stats = Stats::default();
+        for c in cookies {
+            stats.cookies += 1;
+            if is_session(c) {
+                stats.session += 1;
+            } else if c.expiry <= now {
+                stats.expired += 1;
+            } else {
+                let horizon = EXPIRY_HORIZONS
+                    .iter()
+                    .position(|(_, secs)| c.expiry - now < *secs)
+                    .unwrap_or(EXPIRY_HORIZONS.len());
+                stats.expiry[horizon] += 1;
+            }
+            *stats
+                .samesite
+                .entry(c.match_field("SameSite", false))
+                .or_default() += 1;
+            stats.secure += c.secure as usize;
+            stats.http_only += c.http_only as usize;
+            let domain = stats
+                .domains
+                .entry(registrable_domain(&c.host))
+                .or_default();
+            domain.0 += 1;
+            domain.1 += c.value_size();
+        }
+        stats
+    }
+
+    pub fn value_size(&self) -> usize {
+        self.domains.values().map(|(_, size)| size).sum()
+    }
+
+    /// The `n` domains with the largest key, ties are ordered by name
+    fn top_domains<K: Ord>(
+        &self,
+        n: usize,
+        key: impl Fn(&(usize, usize)) -> K,
+    ) -> Vec<(&String, &(usize, usize))> {
+        let mut domains: Vec<_> = self.domains.iter().collect();
+        domains.sort_by(|a, b| key(b.1).cmp(&key(a.1)).then(a.0.cmp(b.0)));
+        domains.truncate(n);
+        domains
+    }
+}
+
+fn percent(part: usize, total: usize) -> String {
+    if total == 0 {
+        return "0%".to_string();
+    }
+    format!("{:.0}%", part as f64 * 100.0 / total as f64)
+}
+
+fn size_str(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// One line of counts for a profile or browser
+fn totals_line(name: &str, width: usize, stats: &Stats) -> String {
+    format!(
+        "  {name:<width$}  {} cookies, {} session, {}",
+        stats.cookies,
+        stats.session,
+        size_str(stats.value_size())
+    )
+}
+
+/// Print totals per profile and browser followed by the
+/// distributions over the cookies of all databases
+pub fn print_stats(cookie_dbs: &[CookieDB], top: usize) {
+    let profiles: Vec<(String, Stats)> = cookie_dbs
+        .iter()
+        .map(|cdb| (cdb.display_name(), Stats::new(cdb.cookies.iter())))
+        .collect();
+    let mut browsers: BTreeMap<&str, Vec<&Cookie>> = BTreeMap::new();
+    for cdb in cookie_dbs {
+        browsers
+            .entry(&cdb.browser)
+            .or_default()
+            .extend(cdb.cookies.iter());
+    }
+    let browsers: Vec<(&str, Stats)> = browsers
+        .into_iter()
+        .map(|(browser, cookies)| (browser, Stats::new(cookies.into_iter())))
+        .collect();
+    let all = Stats::new(cookie_dbs.iter().flat_map(|cdb| cdb.cookies.iter()));
+
+    println!("Profiles");
+    let width = profiles.iter().map(|(n, _)| n.len()).max().unwrap_or(0);
+    for (name, stats) in profiles.iter() {
+        println!("{}", totals_line(name, width, stats));
+    }
+    println!("Browsers");
+    let width = browsers.iter().map(|(n, _)| n.len()).max().unwrap_or(0);
+    for (name, stats) in browsers.iter() {
+        println!("{}", totals_line(name, width, stats));
+    }
+    println!(
+        "Total: {} cookies from {} domains, {} of values\n",
+        all.cookies,
+        all.domains.len(),
+        size_str(all.value_size())
+    );
+
+    let by_count = all.top_domains(top, |(count, _)| *count);
+    let by_size = all.top_domains(top, |(_, size)| *size);
+    for (title, domains) in [("cookies", by_count), ("value size", by_size)] {
+        println!("Top {} domains by {title}", domains.len());
+        let width = domains.iter().map(|(d, _)| d.len()).max().unwrap_or(0);
+        for (domain, (count, size)) in domains {
+            println!(
+                "  {domain:<width$}  {count:>5} cookies  {:>10}",
+                size_str(*size)
+            );
+        }
+    }
+
+    let line = |name: &str, count: usize| {
+        println!(
+            "  {name:<12} {count:>6}  {:>4}",
+            percent(count, all.cookies)
+        );
+    };
+    println!("\nExpiry");
+    line("Session", all.session);
+    line("Expired", all.expired);
+    for (i, (name, _)) in EXPIRY_HORIZONS.iter().enumerate() {
+        line(name, all.expiry[i]);
+    }
+    line(">= 1 year", all.expiry[EXPIRY_HORIZONS.len()]);
+    println!("Persistence");
+    line("Session", all.session);
+    line("Persistent", all.cookies - all.session);
+    println!("SameSite");
+    for (name, count) in all.samesite.iter() {
+        line(name, *count);
+    }
+    println!("Attributes");
+    line("Secure", all.secure);
+    line("HttpOnly", all.http_only);
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::cookie::tests::cookie;
+    use crate::cookie::Cookie;
+    use crate::stats::{Stats, DAY};
+
+    #[test]
+    fn test_stats() {
+        let now = Utc::now().timestamp();
+        let expiring = |host: &str, value: &str, expiry: i64| Cookie {
+            expiry,
+            ..cookie(host, "id", value)
+        };
+        let cookies = [
+            expiring(".example.com", "abc", 0),
+            expiring("www.example.com", "a", now + 2 * DAY),
+            expiring("shop.example.co.uk", "abcdef", now + 400 * DAY),
+            expiring("old.net", "", now - DAY),
+        ];
+        let stats = Stats::new(cookies.iter());
+        assert_eq!(stats.cookies, 4);
+        assert_eq!(stats.session, 1);
+        assert_eq!(stats.expired, 1);
+        assert_eq!(stats.expiry, [0, 1, 0, 0, 1]);
+        assert_eq!(stats.samesite.get("Lax"), Some(&4));
+        assert_eq!(stats.value_size(), 10);
+        assert_eq!(
+            stats.top_domains(2, |(count, _)| *count),
+            [
+                (&"example.com".to_string(), &(2, 4)),
+                (&"example.co.uk".to_string(), &(1, 6))
+            ]
+        );
+        assert_eq!(
+            stats.top_domains(1, |(_, size)| *size),
+            [(&"example.co.uk".to_string(), &(1, 6))]
+        );
+    }
+}
@@ -0,0 +1,636 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use rusqlite::types::Value;
+
+use crate::chromium::ChromiumStore;
+use crate::config::{COMMON_FIELDS, COOKIE_FIELDS, OPTIONAL_FIELDS};
+use crate::cookie::Cookie;
+use crate::error::CookieError;
+use crate::firefox::FirefoxStore;
+use crate::safari::SafariStore;
+use crate::util::DbType;
+
+/// What a cookie store supports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    /// Cookies can be inserted, updated and deleted
+    pub writable: bool,
+    /// Values can be stored encrypted and may need a key to be read
+    pub encrypted: bool,
+}
+
+/// Information about the on-disk format of a cookie store
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    /// Short description of the format, e.g. `SQLite (moz_cookies)`
+    pub format: String,
+    /// Schema version of the database, if it keeps one
+    pub version: Option<i64>,
+    /// The columns of the cookie table, empty for file formats
+    pub columns: Vec<String>,
+}
+
+/// A backend that cookies can be read from and written to. Each supported
+/// database format implements this trait, `CookieDB` only talks to the
+/// store through it.
+pub trait CookieStore {
+    fn capabilities(&self) -> Capabilities;
+
+    fn schema(&self) -> Result<Schema, CookieError>;
+
+    /// Read all cookies from the store
+    fn load(&self) -> Result<Vec<Cookie>, CookieError>;
+
+    /// Read all cookies from the store and leave encrypted values as they
+    /// are, e.g. to delete cookies without looking up a password
+    fn load_encrypted(&self) -> Result<Vec<Cookie>, CookieError> {
+        self.load()
+    }
+
+    /// Add new cookies to the store
+    #[allow(dead_code)]
+    fn insert(&self, cookies: &[Cookie]) -> Result<(), CookieError>;
+
+    /// Overwrite the value, timestamps and flags of existing cookies,
+    /// each cookie is matched on its identity (see `is_same_cookie()`)
+    #[allow(dead_code)]
+    fn update(&self, cookies: &[Cookie]) -> Result<(), CookieError>;
+
+    /// Remove cookies from the store, each cookie is matched on its identity
+    /// so that cookies with the same name in other containers or
+    /// partitions are left untouched
+    fn delete(&self, cookies: &[Cookie]) -> Result<(), CookieError>;
+
+    /// A `SELECT` of the columns of the normalised `cookies` view of the
+    /// `sql` subcommand, from the store attached to `conn` as `schema`.
+    /// Unset for stores that are not SQLite databases.
+    fn sql_select(
+        &self,
+        _conn: &rusqlite::Connection,
+        _schema: &str,
+    ) -> Result<Option<String>, CookieError> {
+        Ok(None)
+    }
+}
+
+/// Fields that are timestamps in the database
+const TIME_FIELDS: &[&str] =
+    &["Creation", "Expiry", "LastAccess", "LastUpdate"];
+
+/// The fields that are columns of the normalised `cookies` view, fields
+/// that are derived from other columns are left out
+pub fn sql_fields() -> Vec<&'static str> {
+    COMMON_FIELDS
+        .iter()
+        .chain(OPTIONAL_FIELDS)
+        .filter(|f| COOKIE_FIELDS[**f] != ["", ""])
+        .copied()
+        .collect()
+}
+
+/// The column name of a field in the normalised `cookies` view,
+/// e.g. `last_access` for `LastAccess`
+pub fn sql_column(field_name: &str) -> String {
+    let mut column = String::new();
+    for (i, c) in field_name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            column.push('_');
+        }
+        column.push(c.to_ascii_lowercase());
+    }
+    column
+}
+
+/// Open the store for a database of the given type
+pub fn open_store(
+    typing: &DbType,
+    path: &Path,
+    browser: &str,
+) -> Result<Box<dyn CookieStore>, CookieError> {
+    match typing {
+        DbType::Firefox => Ok(Box::new(FirefoxStore::new(path))),
+        DbType::Chrome => Ok(Box::new(ChromiumStore::new(path, browser))),
+        DbType::Safari => Ok(Box::new(SafariStore::new(path))),
+        DbType::Unknown => Err(CookieError::Format(format!(
+            "Unknown database type: {}",
+            path.display()
+        ))),
+    }
+}
+
+/// A column from `PRAGMA table_info`
+struct Column {
+    name: String,
+    decl_type: String,
+    /// Set for `NOT NULL` columns without a default value
+    required: bool,
+}
+
+/// A cookie table in a SQLite database, shared by the Firefox and
+/// Chromium stores. The column of each field is looked up in
+/// `COOKIE_FIELDS`.
+pub struct SqliteTable {
+    pub path: PathBuf,
+    pub table: &'static str,
+    /// Index of the browser in the `COOKIE_FIELDS` columns
+    pub field_idx: usize,
+    /// Timestamps are stored as microseconds since an epoch, this is
+    /// the number of seconds from that epoch to the UNIX epoch
+    pub epoch_offset: i64,
+    /// Timestamp fields that are stored in seconds instead of microseconds
+    pub seconds_fields: &'static [&'static str],
+    /// Column of the encrypted value, if the browser has one
+    pub encrypted_column: Option<&'static str>,
+    /// Query for the schema version of the database
+    pub version_query: &'static str,
+}
+
+impl SqliteTable {
+    /// The number of units per second of the timestamps of a field
+    fn time_scale(&self, field_name: &str) -> i64 {
+        if self.seconds_fields.contains(&field_name) {
+            1
+        } else {
+            1_000_000
+        }
+    }
+
+    /// Convert a timestamp of a field from the database into UNIX epoch
+    /// time. Cookies with a Session-only lifetime will have 0 as their
+    /// expiry date in Chrome.
+    fn to_unix_epoch(&self, field_name: &str, timestamp: i64) -> i64 {
+        if timestamp == 0 {
+            0
+        } else {
+            (timestamp / self.time_scale(field_name)) - self.epoch_offset
+        }
+    }
+
+    /// Convert a UNIX epoch time into a timestamp of a field
+    /// for the database
+    fn db_timestamp(&self, field_name: &str, epoch: i64) -> i64 {
+        if epoch == 0 {
+            0
+        } else {
+            (epoch + self.epoch_offset) * self.time_scale(field_name)
+        }
+    }
+
+    fn column(&self, field_name: &str) -> &'static str {
+        COOKIE_FIELDS[field_name][self.field_idx]
+    }
+
+    fn open_read_only(&self) -> Result<rusqlite::Connection, CookieError> {
+        Ok(rusqlite::Connection::open_with_flags(
+            &self.path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )?)
+    }
+
+    /// The columns of the table in the database `schema` of `conn`,
+    /// `main` unless the database is attached
+    fn table_info(
+        &self,
+        conn: &rusqlite::Connection,
+        schema: &str,
+    ) -> Result<Vec<Column>, CookieError> {
+        let mut stmt = conn
+            .prepare(&format!("PRAGMA {schema}.table_info({})", self.table))?;
+        let columns = stmt
+            .query_map([], |row| {
+                Ok(Column {
+                    name: row.get(1)?,
+                    decl_type: row.get(2)?,
+                    required: row.get::<_, bool>(3)?
+                        && row.get::<_, Option<String>>(4)?.is_none()
+                        && !row.get::<_, bool>(5)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok(columns)
+    }
+
+    /// The schema version, stored as either an integer or a string
+    pub fn version(&self, conn: &rusqlite::Connection) -> Option<i64> {
+        conn.query_row(self.version_query, [], |row| row.get::<_, Value>(0))
+            .ok()
+            .and_then(|v| match v {
+                Value::Integer(i) => Some(i),
+                Value::Text(t) => t.parse().ok(),
+                _ => None,
+            })
+    }
+
+    pub fn schema(&self) -> Result<Schema, CookieError> {
+        let conn = self.open_read_only()?;
+        Ok(Schema {
+            format: format!("SQLite ({})", self.table),
+            version: self.version(&conn),
+            columns: self
+                .table_info(&conn, "main")?
+                .into_iter()
+                .map(|c| c.name)
+                .collect(),
+        })
+    }
+
+    /// The column name of a field in the current database or `NULL`
+    /// if the field does not exist for this browser or schema version
+    fn column_or_null(
+        &self,
+        columns: &HashSet<String>,
+        field_name: &str,
+    ) -> &'static str {
+        let column = self.column(field_name);
+        if columns.contains(column) {
+            column
+        } else {
+            "NULL"
+        }
+    }
+
+    /// Load all cookies from the table, the schema version
+    /// of the database is returned alongside them
+    pub fn load(&self) -> Result<(Vec<Cookie>, Option<i64>), CookieError> {
+        let conn = self.open_read_only()?;
+
+        // Browser specific columns differ between schema versions,
+        // only select those that exist in the current database
+        let columns: HashSet<String> = self
+            .table_info(&conn, "main")?
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+
+        let query = format!(
+            "SELECT {},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{} FROM {};",
+            self.column("Host"),
+            self.column("Name"),
+            self.column("Value"),
+            self.column("Path"),
+            self.column("Creation"),
+            self.column("Expiry"),
+            self.column("LastAccess"),
+            self.column("HttpOnly"),
+            self.column("Secure"),
+            self.column("SameSite"),
+            self.encrypted_column.unwrap_or("NULL"),
+            self.column_or_null(&columns, "OriginAttributes"),
+            self.column_or_null(&columns, "SchemeMap"),
+            self.column_or_null(&columns, "PartitionedAttributeSet"),
+            self.column_or_null(&columns, "Priority"),
+            self.column_or_null(&columns, "SourceScheme"),
+            self.column_or_null(&columns, "SourcePort"),
+            self.column_or_null(&columns, "IsPersistent"),
+            self.column_or_null(&columns, "HasExpires"),
+            self.column_or_null(&columns, "TopFrameSiteKey"),
+            self.column_or_null(&columns, "LastUpdate"),
+            self.table
+        );
+        let mut stmt = conn.prepare(&query)?;
+        let results_iter = stmt.query_map([], |row| {
+            // The second parameter to get() denotes
+            // the underlying type that the fetched field is expected to have
+            //
+            // We use .unwrap() to get notified explicitly notified
+            // of parsing failures
+            Ok(Cookie {
+                host: row.get::<_, String>(0).unwrap(),
+                name: row.get::<_, String>(1).unwrap(),
+                value: row.get::<_, String>(2).unwrap(),
+                path: row.get::<_, String>(3).unwrap(),
+                creation: self.to_unix_epoch(
+                    "Creation",
+                    row.get::<_, i64>(4).unwrap(),
+                ),
+                expiry: self
+                    .to_unix_epoch("Expiry", row.get::<_, i64>(5).unwrap()),
+                last_access: self.to_unix_epoch(
+                    "LastAccess",
+                    row.get::<_, i64>(6).unwrap(),
+                ),
+                http_only: row.get::<_, bool>(7).unwrap(),
+                secure: row.get::<_, bool>(8).unwrap(),
+                samesite: row.get::<_, i32>(9).unwrap(),
+                encrypted_value: row.get::<_, Vec<u8>>(10).unwrap_or(vec![]),
+                origin_attributes: row.get::<_, Option<String>>(11).unwrap(),
+                scheme_map: row.get::<_, Option<i64>>(12).unwrap(),
+                is_partitioned_attribute_set: row
+                    .get::<_, Option<bool>>(13)
+                    .unwrap(),
+                priority: row.get::<_, Option<i64>>(14).unwrap(),
+                source_scheme: row.get::<_, Option<i64>>(15).unwrap(),
+                source_port: row.get::<_, Option<i64>>(16).unwrap(),
+                is_persistent: row.get::<_, Option<bool>>(17).unwrap(),
+                has_expires: row.get::<_, Option<bool>>(18).unwrap(),
+                top_frame_site_key: row.get::<_, Option<String>>(19).unwrap(),
+                last_update: row
+                    .get::<_, Option<i64>>(20)
+                    .unwrap()
+                    .map(|t| self.to_unix_epoch("LastUpdate", t)),
+                container: None,
+                from_session_store: false,
+            })
+        })?;
+
+        // The query_map() call returns an iterator
+        // of results, Ok(), which we need to unwrap
+        // before calling collect
+        let cookies = results_iter.filter_map(|r| r.ok()).collect();
+        let version = self.version(&conn);
+
+        stmt.finalize()?;
+        conn.close().map_err(|(_, err)| err)?;
+        Ok((cookies, version))
+    }
+
+    /// The value of a field of a cookie in the representation of the
+    /// database, `None` for fields that are unset
+    fn field_value(&self, cookie: &Cookie, field_name: &str) -> Option<Value> {
+        let epoch =
+            |t: i64| Value::Integer(self.db_timestamp(field_name, t));
+        let flag = |b: bool| Value::Integer(b as i64);
+        match field_name {
+            "Host" => Some(Value::Text(cookie.host.to_owned())),
+            "Name" => Some(Value::Text(cookie.name.to_owned())),
+            "Value" => Some(Value::Text(cookie.value.to_owned())),
+            "Path" => Some(Value::Text(cookie.path.to_owned())),
+            "Creation" => Some(epoch(cookie.creation)),
+            "Expiry" => Some(epoch(cookie.expiry)),
+            "LastAccess" => Some(epoch(cookie.last_access)),
+            "HttpOnly" => Some(flag(cookie.http_only)),
+            "Secure" => Some(flag(cookie.secure)),
+            "SameSite" => Some(Value::Integer(cookie.samesite as i64)),
+            "OriginAttributes" => {
+                cookie.origin_attributes.clone().map(Value::Text)
+            }
+            "SchemeMap" => cookie.scheme_map.map(Value::Integer),
+            "PartitionedAttributeSet" => {
+                cookie.is_partitioned_attribute_set.map(flag)
+            }
+            "Priority" => cookie.priority.map(Value::Integer),
+            "SourceScheme" => cookie.source_scheme.map(Value::Integer),
+            "SourcePort" => cookie.source_port.map(Value::Integer),
+            "IsPersistent" => cookie.is_persistent.map(flag),
+            "HasExpires" => cookie.has_expires.map(flag),
+            "TopFrameSiteKey" => {
+                cookie.top_frame_site_key.clone().map(Value::Text)
+            }
+            "LastUpdate" => cookie.last_update.map(epoch),
+            _ => None,
+        }
+    }
+
+    /// The condition that matches a cookie on its identity, the parameters
+    /// for the condition start at `?1`
+    fn identity_condition(&self, cookie: &Cookie) -> (String, Vec<Value>) {
+        let mut condition = format!(
+            "{} = ?1 AND {} = ?2 AND {} = ?3",
+            self.column("Host"),
+            self.column("Name"),
+            self.column("Path"),
+        );
+        let mut params = vec![
+            Value::Text(cookie.host.to_owned()),
+            Value::Text(cookie.name.to_owned()),
+            Value::Text(cookie.path.to_owned()),
+        ];
+        // Firefox keeps the partition key inside of `originAttributes`,
+        // Chromium uses a separate column. Without this condition,
+        // partitioned copies of a cookie would be matched as well.
+        for field_name in ["OriginAttributes", "TopFrameSiteKey"] {
+            if let Some(value) = self.field_value(cookie, field_name) {
+                condition += &format!(" AND {} = ?4", self.column(field_name));
+                params.push(value);
+                break;
+            }
+        }
+        (condition, params)
+    }
+
+    /// See `CookieStore::sql_select()`, timestamps are converted
+    /// to UNIX epoch time
+    pub fn sql_select(
+        &self,
+        conn: &rusqlite::Connection,
+        schema: &str,
+    ) -> Result<String, CookieError> {
+        let columns: HashSet<String> = self
+            .table_info(conn, schema)?
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+        let exprs: Vec<String> = sql_fields()
+            .into_iter()
+            .map(|field_name| {
+                let column = self.column(field_name);
+                let expr = if !columns.contains(column) {
+                    "NULL".to_string()
+                } else if TIME_FIELDS.contains(&field_name) {
+                    format!(
+                        "CASE WHEN {column} = 0 THEN 0 \
+                         ELSE {column} / {} - {} END",
+                        self.time_scale(field_name),
+                        self.epoch_offset
+                    )
+                } else {
+                    column.to_string()
+                };
+                format!("{expr} AS {}", sql_column(field_name))
+            })
+            .collect();
+        Ok(format!(
+            "SELECT {} FROM {schema}.{}",
+            exprs.join(", "),
+            self.table
+        ))
+    }
+
+    /// Insert cookies with all fields that exist in the table. Other
+    /// `NOT NULL` columns without a default are set to an empty value.
+    pub fn insert(&self, cookies: &[Cookie]) -> Result<(), CookieError> {
+        let mut conn = rusqlite::Connection::open(&self.path)?;
+        let table_info = self.table_info(&conn, "main")?;
+        let tx = conn.transaction()?;
+        for c in cookies {
+            let mut columns = vec![];
+            let mut params = vec![];
+            for field_name in COOKIE_FIELDS.keys() {
+                let column = self.column(field_name);
+                if column.is_empty()
+                    || !table_info.iter().any(|col| col.name == column)
+                {
+                    continue;
+                }
+                if let Some(value) = self.field_value(c, field_name) {
+                    columns.push(column.to_string());
+                    params.push(value);
+                }
+            }
+            if let Some(column) = self.encrypted_column {
+                columns.push(column.to_string());
+                params.push(Value::Blob(c.encrypted_value.to_owned()));
+            }
+            for col in table_info.iter().filter(|col| col.required) {
+                if !columns.contains(&col.name) {
+                    let empty = match col.decl_type.to_uppercase().as_str() {
+                        "TEXT" => Value::Text(String::new()),
+                        "BLOB" => Value::Blob(vec![]),
+                        _ => Value::Integer(0),
+                    };
+                    columns.push(col.name.to_owned());
+                    params.push(empty);
+                }
+            }
+
+            let placeholders: Vec<String> =
+                (1..=params.len()).map(|i| format!("?{i}")).collect();
+            tx.execute(
+                &format!(
+                    "INSERT INTO {} ({}) VALUES ({});",
+                    self.table,
+                    columns.join(","),
+                    placeholders.join(",")
+                ),
+                rusqlite::params_from_iter(params),
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn update(&self, cookies: &[Cookie]) -> Result<(), CookieError> {
+        let mut conn = rusqlite::Connection::open(&self.path)?;
+        let tx = conn.transaction()?;
+        for c in cookies {
+            let (condition, mut params) = self.identity_condition(c);
+            let mut assignments = vec![];
+            for field_name in [
+                "Value",
+                "Expiry",
+                "LastAccess",
+                "HttpOnly",
+                "Secure",
+                "SameSite",
+            ] {
+                params.push(self.field_value(c, field_name).unwrap());
+                assignments.push(format!(
+                    "{} = ?{}",
+                    self.column(field_name),
+                    params.len()
+                ));
+            }
+            if let Some(column) = self.encrypted_column {
+                params.push(Value::Blob(c.encrypted_value.to_owned()));
+                assignments.push(format!("{column} = ?{}", params.len()));
+            }
+            tx.execute(
+                &format!(
+                    "UPDATE {} SET {} WHERE {condition};",
+                    self.table,
+                    assignments.join(", ")
+                ),
+                rusqlite::params_from_iter(params),
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn delete(&self, cookies: &[Cookie]) -> Result<(), CookieError> {
+        let mut conn = rusqlite::Connection::open(&self.path)?;
+        let tx = conn.transaction()?;
+        for c in cookies {
+            let (condition, params) = self.identity_condition(c);
+            tx.execute(
+                &format!("DELETE FROM {} WHERE {condition};", self.table),
+                rusqlite::params_from_iter(params),
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cookie::Cookie;
+    use crate::store::SqliteTable;
+
+    #[test]
+    fn test_sqlite_table_round_trip() {
+        let path = std::env::temp_dir().join("cookiecutter_test_store.sqlite");
+        let _ = std::fs::remove_file(&path);
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE meta (key TEXT, value TEXT);
+             INSERT INTO meta VALUES ('version', '24');
+             CREATE TABLE cookies (creation_utc INTEGER NOT NULL,
+                host_key TEXT NOT NULL, top_frame_site_key TEXT NOT NULL,
+                name TEXT NOT NULL, value TEXT NOT NULL,
+                encrypted_value BLOB NOT NULL, path TEXT NOT NULL,
+                expires_utc INTEGER NOT NULL, is_secure INTEGER NOT NULL,
+                is_httponly INTEGER NOT NULL,
+                last_access_utc INTEGER NOT NULL,
+                samesite INTEGER NOT NULL, source_port INTEGER NOT NULL,
+                last_update_utc INTEGER NOT NULL);",
+        )
+        .unwrap();
+        conn.close().unwrap();
+
+        let table = SqliteTable {
+            path: path.clone(),
+            table: "cookies",
+            field_idx: 0,
+            epoch_offset: 11_644_473_600,
+            seconds_fields: &[],
+            encrypted_column: Some("encrypted_value"),
+            version_query: "SELECT value FROM meta WHERE key = 'version';",
+        };
+        let mut cookie = Cookie {
+            host: ".example.com".to_string(),
+            name: "sid".to_string(),
+            value: "abc".to_string(),
+            path: "/".to_string(),
+            creation: 1_700_000_000,
+            expiry: 1_800_000_000,
+            last_access: 1_700_000_000,
+            http_only: true,
+            secure: true,
+            samesite: 1,
+            top_frame_site_key: Some(String::new()),
+            ..Default::default()
+        };
+        let mut partitioned = cookie.clone();
+        partitioned.top_frame_site_key = Some("https://site.org".to_string());
+        table
+            .insert(&[cookie.clone(), partitioned.clone()])
+            .unwrap();
+
+        let (cookies, version) = table.load().unwrap();
+        assert_eq!(version, Some(24));
+        assert_eq!(cookies.len(), 2);
+        assert_eq!(cookies[0].expiry, 1_800_000_000);
+        assert_eq!(cookies[0].source_port, Some(0));
+        assert!(cookies[0].http_only && cookies[0].secure);
+
+        cookie.value = "def".to_string();
+        table.update(&[cookie.clone()]).unwrap();
+        let (cookies, _) = table.load().unwrap();
+        assert_eq!(cookies[0].value, "def");
+        assert_eq!(cookies[1].value, "abc");
+
+        table.delete(&[partitioned]).unwrap();
+        let (cookies, _) = table.load().unwrap();
+        assert_eq!(cookies.len(), 1);
+        assert!(cookies[0].is_same_cookie(&cookie));
+
+        let schema = table.schema().unwrap();
+        assert_eq!(schema.version, Some(24));
+        assert!(schema.columns.contains(&"top_frame_site_key".to_string()));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -6,7 +6,7 @@ use crossterm::{
         LeaveAlternateScreen,
     },
 };
-use std::{fs::OpenOptions, io, io::Write, time::Duration, time::Instant};
+use std::{cmp::Reverse, io, time::Duration, time::Instant};
 use tui::{
     backend::{Backend, CrosstermBackend},
     layout::{Constraint, Direction, Layout, Rect},
@@ -20,12 +20,15 @@ use tui::{
 
 use crate::{
     config::{
-        Config, DEBUG_LOG, NO_SELECTION, TUI_PRIMARY_COLOR, TUI_SEARCH,
+        Config, NO_SELECTION, OPTIONAL_FIELDS, TUI_PRIMARY_COLOR, TUI_SEARCH,
         TUI_TEXT_TRUNCATE_LIM,
     },
+    cookie::Cookie,
     cookie_db::CookieDB,
-    state::{Selection, State},
-    util::copy_to_clipboard,
+    sort::cmp_cookies,
+    state::{CookieOrder, DomainOrder, Selection, State},
+    util::{copy_to_clipboard, debug_log},
+    watcher::Reloader,
 };
 
 /// Entrypoint for the TUI
@@ -70,8 +73,21 @@ fn run_ui<B: Backend>(
         state.profiles.status.select(Some(0));
     }
 
+    // Reload databases that are modified by the browser
+    let mut reloader = Reloader::new(&cookie_dbs).unwrap_or_else(|err| {
+        debug_log(format!("Failed to watch databases: {err}"));
+        Reloader::disabled()
+    });
+
     loop {
-        term.draw(|f| ui(f, state, &cookie_dbs))?;
+        for i in reloader.poll(&mut cookie_dbs) {
+            debug_log(format!("Reloaded {}", cookie_dbs[i].path_short()));
+            if state.profiles.status.selected() == Some(i) {
+                state.stale = true;
+            }
+        }
+        refresh_lists(state, &mut cookie_dbs);
+        term.draw(|f| ui(f, state))?;
 
         let timeout = tick_rate
             .checked_sub(last_tick.elapsed())
@@ -106,11 +122,7 @@ fn run_ui<B: Backend>(
 ///  |0       |1      |2           |3         |
 ///  |profiles|domains|cookie names|field_list|
 ///
-fn ui<B: Backend>(
-    frame: &mut Frame<B>,
-    state: &mut State,
-    cookie_dbs: &Vec<CookieDB>,
-) {
+fn ui<B: Backend>(frame: &mut Frame<B>, state: &mut State) {
     // == Layout ==//
     // Split the frame vertically into a body and footer
     let vert_chunks = Layout::default()
@@ -170,94 +182,176 @@ fn ui<B: Backend>(
     }
 
     //== Domains ==//
-    if let Some(profile_idx) = state.profiles.status.selected() {
-        if let Some(cdb) = cookie_dbs.get(profile_idx) {
-            // Fill the current_domains state list
-            state.current_domains.items = cdb.domains();
-
-            let domain_items = create_list_items(&state.current_domains.items);
-
-            let domain_list = add_highlight(create_list(
-                domain_items,
-                "Domains".to_string(),
-                Borders::NONE,
-            ));
-
-            //== Render domains ==//
-            frame.render_stateful_widget(
-                domain_list,
-                chunks[domains_idx],
-                &mut state.current_domains.status,
-            );
-
-            //== Cookies ==//
-            if let Some(current_domain) = state.selected_domain() {
-                // Fill the current_cookies state list
-                state.current_cookies.items = cdb
-                    .cookies_for_domain(&current_domain)
-                    .iter()
-                    .map(|c| c.name.to_owned())
-                    .collect();
-
-                let cookies_items =
-                    create_list_items(&state.current_cookies.items);
-
-                let cookies_list = add_highlight(create_list(
-                    cookies_items,
-                    "Cookies".to_string(),
-                    Borders::NONE,
-                ));
+    if state.profiles.status.selected().is_some() {
+        let domain_items = create_list_items(&state.current_domains.items);
 
-                //== Render cookies ==//
-                frame.render_stateful_widget(
-                    cookies_list,
-                    chunks[cookies_idx],
-                    &mut state.current_cookies.status,
-                );
-
-                //== Fields ==//
-                if let Some(current_cookie) = state.selected_cookie() {
-                    if let Some(cookie) =
-                        cdb.cookie_for_domain(&current_cookie, &current_domain)
-                    {
-                        // Fill the current_fields state list
-                        state.current_fields.items = vec![
-                            cookie.match_field("Value", true),
-                            cookie.match_field("Path", true),
-                            cookie.match_field("Creation", true),
-                            cookie.match_field("Expiry", true),
-                            cookie.match_field("LastAccess", true),
-                            cookie.match_field("HttpOnly", true),
-                            cookie.match_field("Secure", true),
-                            cookie.match_field("SameSite", true),
-                        ];
-
-                        // Create list items for the UI
-                        let fields_items: Vec<ListItem> =
-                            create_list_items(&state.current_fields.items);
-
-                        let fields_list = create_list(
-                            fields_items,
-                            "Fields".to_string(),
-                            Borders::ALL,
-                        );
-
-                        if fields_idx != NO_SELECTION {
-                            //== Render fields ==//
-                            frame.render_stateful_widget(
-                                fields_list,
-                                chunks[fields_idx],
-                                &mut state.current_fields.status,
-                            );
-                            if state.current_fields.items.len() > 0 {
-                                state.current_fields.status.select(Some(0));
-                            }
-                        }
-                    }
-                }
-            }
+        let domain_list = add_highlight(create_list(
+            domain_items,
+            format!("Domains ({})", state.domain_order.label()),
+            Borders::NONE,
+        ));
+
+        //== Render domains ==//
+        frame.render_stateful_widget(
+            domain_list,
+            chunks[domains_idx],
+            &mut state.current_domains.status,
+        );
+    }
+
+    //== Cookies ==//
+    if state.selected_domain().is_some() {
+        let cookies_items = create_list_items(&state.current_cookies.items);
+
+        let cookies_list = add_highlight(create_list(
+            cookies_items,
+            format!("Cookies ({})", state.cookie_order.label()),
+            Borders::NONE,
+        ));
+
+        //== Render cookies ==//
+        frame.render_stateful_widget(
+            cookies_list,
+            chunks[cookies_idx],
+            &mut state.current_cookies.status,
+        );
+    }
+
+    //== Fields ==//
+    if fields_idx != NO_SELECTION && !state.current_fields.items.is_empty() {
+        // Create list items for the UI
+        let fields_items: Vec<ListItem> =
+            create_list_items(&state.current_fields.items);
+
+        let fields_list =
+            create_list(fields_items, "Fields".to_string(), Borders::ALL);
+
+        //== Render fields ==//
+        frame.render_stateful_widget(
+            fields_list,
+            chunks[fields_idx],
+            &mut state.current_fields.status,
+        );
+        state.current_fields.status.select(Some(0));
+    }
+}
+
+/// Fill the domain, cookie and field lists for the current selection.
+/// Nothing is recomputed unless the selection changed since the last
+/// call or the selected database was modified. The cookies of each
+/// profile are only loaded once it is selected for the first time.
+/// After a modification, the selected domain and cookie are kept
+/// selected if they still exist.
+fn refresh_lists(state: &mut State, cookie_dbs: &mut [CookieDB]) {
+    let selection = state.list_selection();
+    if !state.stale && state.shown == Some(selection) {
+        return;
+    }
+    let shown = state.shown.unwrap_or_default();
+    let profile_changed = state.stale || shown.0 != selection.0;
+    let domain_changed = profile_changed || shown.1 != selection.1;
+    state.stale = false;
+
+    let Some(cdb) = selection.0.and_then(|i| cookie_dbs.get_mut(i)) else {
+        state.current_domains.items.clear();
+        state.current_cookies.items.clear();
+        state.current_fields.items.clear();
+        state.shown = Some(selection);
+        return;
+    };
+    if !cdb.loaded {
+        debug_log(format!("Loading {}", cdb.path_short()));
+        if let Err(err) = cdb.load_cookies() {
+            debug_log(format!("Failed to load {}: {err}", cdb.path_short()));
         }
+        cdb.retain_matching(&Config::global().filter);
+    }
+
+    if profile_changed {
+        state
+            .current_domains
+            .replace_items(sorted_domains(cdb, state.domain_order));
+    }
+    let domain_cookies = state
+        .selected_domain()
+        .map(|d| domain_cookies(cdb, &d, state.cookie_order))
+        .unwrap_or_default();
+    if domain_changed {
+        state.current_cookies.replace_items(
+            domain_cookies.iter().map(|c| cookie_label(c)).collect(),
+        );
+    }
+    state.current_fields.items = state
+        .current_cookies
+        .status
+        .selected()
+        .and_then(|i| domain_cookies.get(i))
+        .map(|c| cookie_fields(c))
+        .unwrap_or_default();
+
+    // Leave splits that became empty
+    if state.selection == Selection::Cookies
+        && state.current_cookies.status.selected().is_none()
+    {
+        state.selection = Selection::Domains;
+    }
+    if state.selection == Selection::Domains
+        && state.current_domains.status.selected().is_none()
+    {
+        state.selection = Selection::Profiles;
+    }
+    state.shown = Some(state.list_selection());
+}
+
+/// The domains of a database in the order of the domains split,
+/// domains that compare as equal are ordered by name
+fn sorted_domains(cdb: &CookieDB, order: DomainOrder) -> Vec<String> {
+    let mut domains = cdb.domains();
+    match order {
+        DomainOrder::Name => {}
+        DomainOrder::Count => domains
+            .sort_by_cached_key(|d| Reverse(cdb.cookies_for_domain(d).len())),
+        DomainOrder::LastAccess => domains.sort_by_cached_key(|d| {
+            let cookies = cdb.cookies_for_domain(d);
+            Reverse(cookies.iter().map(|c| c.last_access).max())
+        }),
     }
+    domains
+}
+
+/// The cookies of a domain in the order of the cookies split
+fn domain_cookies<'a>(
+    cdb: &'a CookieDB,
+    domain: &str,
+    order: CookieOrder,
+) -> Vec<&'a Cookie> {
+    let mut cookies = cdb.cookies_for_domain(domain);
+    let keys = order.sort_keys();
+    cookies.sort_by(|a, b| cmp_cookies(&keys, a, b));
+    cookies
+}
+
+/// The fields shown for the selected cookie
+fn cookie_fields(cookie: &Cookie) -> Vec<String> {
+    let mut fields = vec![
+        cookie.match_field("Value", true),
+        cookie.match_field("Path", true),
+        cookie.match_field("Creation", true),
+        cookie.match_field("Expiry", true),
+        cookie.match_field("LastAccess", true),
+        cookie.match_field("HttpOnly", true),
+        cookie.match_field("Secure", true),
+        cookie.match_field("SameSite", true),
+    ];
+    // Browser specific fields are only shown if
+    // they exist in the underlying database
+    fields.extend(
+        OPTIONAL_FIELDS
+            .iter()
+            .map(|f| cookie.match_field(f, true))
+            .filter(|f| !f.is_empty()),
+    );
+    fields
 }
 
 fn handle_search_key(
@@ -275,7 +369,9 @@ fn handle_search_key(
                 Selection::Profiles => {
                     // Save all partial matches
                     for (i, p) in cookie_dbs.iter().enumerate() {
-                        if p.path.to_string_lossy().contains(&query) {
+                        if p.path.to_string_lossy().contains(&query)
+                            || p.display_name().contains(&query)
+                        {
                             state.search_matches.push(i);
                         }
                     }
@@ -397,7 +493,7 @@ fn handle_key(
                 }
                 Selection::Cookies => {
                     // The `state.current_fields.items` array is empty
-                    // until the next ui() tick.
+                    // until the next refresh.
                     // This branch is needed to make the `match` exhaustive.
                 }
             }
@@ -439,18 +535,51 @@ fn handle_key(
             if let Some(profile_idx) = state.profiles.status.selected() {
                 if let Some(cdb) = cookie_dbs.get_mut(profile_idx) {
                     if let Some(current_domain) = state.selected_domain() {
-                        delete_in_current_split(state, cdb, current_domain)
+                        delete_in_current_split(state, cdb, current_domain);
+                        state.stale = true;
                     }
                 }
             }
         }
+        //== Cycle the order of the current split ==//
+        KeyCode::Char('s') => {
+            match state.selection {
+                Selection::Profiles => return,
+                Selection::Domains => {
+                    state.domain_order = state.domain_order.next()
+                }
+                Selection::Cookies => {
+                    state.cookie_order = state.cookie_order.next()
+                }
+            }
+            // Indices of earlier matches refer to the old order,
+            // the selected item is kept selected on the refresh
+            state.search_matches.clear();
+            state.selected_match = NO_SELECTION;
+            state.stale = true;
+        }
+        //== Reload the current profile ==//
+        KeyCode::Char('r') => {
+            let profile_idx = state.profiles.status.selected();
+            if let Some(cdb) = profile_idx.and_then(|i| cookie_dbs.get_mut(i))
+            {
+                debug_log(format!("Reloading {}", cdb.path_short()));
+                cdb.loaded = false;
+                state.stale = true;
+            }
+        }
         //== Copy value to clipboard ==//
         KeyCode::Char('C') => {
             match state.selection {
                 Selection::Profiles => {
                     // Copy the path to the current profile
-                    copy_to_clipboard(state.selected_profile().unwrap())
-                        .expect("Clipboard copy failed");
+                    let profile_idx = state.profiles.status.selected();
+                    if let Some(cdb) =
+                        profile_idx.and_then(|i| cookie_dbs.get(i))
+                    {
+                        copy_to_clipboard(cdb.path_short())
+                            .expect("Clipboard copy failed");
+                    }
                 }
                 Selection::Domains => {
                     // Copy the name of the current domain
@@ -477,21 +606,34 @@ fn handle_key(
 /// Delete the currently selected cookie if in the `Cookies` split
 /// and all cookies from a domain if inside the `Domains` split
 /// To update the internal cookie_db requires a mutable reference
+/// Nothing is deleted from read-only databases
 fn delete_in_current_split(
     state: &mut State,
     cdb: &mut CookieDB,
     current_domain: String,
 ) {
+    if cdb.is_read_only() {
+        debug_log(format!(
+            "Skipping deletion from read-only {}",
+            cdb.path_short()
+        ));
+        return;
+    }
     match state.selection {
         // Remove all cookies from the current domain
         Selection::Domains => {
             debug_log(format!("Deleting: {current_domain}"));
-            cdb.delete_from_domain(&current_domain, "")
+            let domain_cookies: Vec<Cookie> = cdb
+                .cookies_for_domain(&current_domain)
+                .into_iter()
+                .cloned()
+                .collect();
+            cdb.delete_cookies(&domain_cookies)
                 .expect("Failed to delete cookies from domain");
 
             // If the removed item was the last domain,
             // unselect the domains split
-            // The length will be non-decremented until the next `ui()` tick
+            // The length will be non-decremented until the next refresh
             if state.current_domains.items.len() == 1 {
                 state.current_domains.status.select(None)
             } else if let Some(sel) = state.current_domains.status.selected() {
@@ -504,11 +646,24 @@ fn delete_in_current_split(
         }
         // Remove a specific cookie from the current domain
         Selection::Cookies => {
-            if let Some(current_cookie) = state.selected_cookie() {
+            let selected_cookie = state
+                .current_cookies
+                .status
+                .selected()
+                .and_then(|i| {
+                    domain_cookies(cdb, &current_domain, state.cookie_order)
+                        .get(i)
+                        .map(|c| (*c).clone())
+                });
+
+            if let Some(cookie) = selected_cookie {
                 debug_log(format!(
-                    "Deleting: {current_domain}.{current_cookie}"
+                    "Deleting: {current_domain}.{}",
+                    cookie_label(&cookie)
                 ));
-                cdb.delete_from_domain(&current_domain, &current_cookie)
+                // Only the selected cookie is removed, cookies with the
+                // same name from other containers are kept
+                cdb.delete_cookies(&[cookie])
                     .expect("Failed to delete cookie");
 
                 // If the removed item was the last cookie,
@@ -575,6 +730,23 @@ fn set_matches(
     search_matches.len() != 0
 }
 
+/// The text shown for a cookie in the cookies split, cookies
+/// from a Firefox container are suffixed with the container name
+/// and partitioned cookies with their partition key
+fn cookie_label(cookie: &Cookie) -> String {
+    let mut label = cookie.name.to_owned();
+    if let Some(container) = &cookie.container {
+        label += &format!(" [{container}]");
+    }
+    if let Some(partition_key) = cookie.partition_key() {
+        label += &format!(" ({partition_key})");
+    }
+    if cookie.from_session_store {
+        label += " (session)";
+    }
+    label
+}
+
 //============================================================================//
 
 /// Create list items for the UI
@@ -623,6 +795,8 @@ fn create_footer() -> Table<'static> {
         Cell::from("D: Delete").style(Style::default().fg(Color::LightRed)),
         Cell::from("C: Copy to clipboard")
             .style(Style::default().fg(Color::LightYellow)),
+        Cell::from("s: Sort"),
+        Cell::from("r: Reload"),
         Cell::from("q: Quit"),
     ];
 
@@ -635,6 +809,8 @@ fn create_footer() -> Table<'static> {
             Constraint::Percentage(7),
             Constraint::Percentage(12),
             Constraint::Percentage(7),
+            Constraint::Percentage(7),
+            Constraint::Percentage(7),
         ])
 }
 
@@ -661,16 +837,3 @@ fn create_list(items: Vec<ListItem>, title: String, border: Borders) -> List {
             )),
     )
 }
-
-/// Print a debug message to `DEBUG_LOG`
-fn debug_log<T: std::fmt::Display>(msg: T) {
-    if Config::global().debug {
-        let mut f = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(DEBUG_LOG)
-            .expect("Failed to open debug log");
-
-        writeln!(f, "-> {msg}").expect("Failed to write debug message");
-    }
-}
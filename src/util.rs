@@ -1,36 +1,119 @@
 use std::io;
 use std::{
-    collections::HashSet,
     env::consts,
     fs::{File, OpenOptions},
     io::{BufRead, Read, Write},
-    path::Path,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
 };
 
-use walkdir::WalkDir;
-
 use sysinfo::{ProcessRefreshKind, RefreshKind, System};
 
-use crate::config::{DB_NAMES, SEARCH_DIRS, SQLITE_FILE_ID};
-use crate::cookie_db::CookieDB;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    Config, COUNTRY_SECOND_LEVEL_DOMAINS, DEBUG_LOG, SAFARI_MAGIC,
+    SQLITE_FILE_ID, WINDOWS_SHARED_USER_DIRS, WSL_OSRELEASE, WSL_WINDOWS_USERS,
+};
 
 /// The PartialEq trait allows us to use `matches!` to check
 /// equality between enums
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DbType {
+    #[serde(alias = "chromium")]
     Chrome,
     Firefox,
+    /// Safari's `Cookies.binarycookies`, which can only be read
+    Safari,
+    #[serde(skip)]
     Unknown,
 }
 
-/// Returns /mnt/c/Users/$USER under WSL, otherwise the value of $HOME
-pub fn get_home() -> String {
-    if std::fs::metadata("/mnt/c/Users").is_ok() {
-        format!("/mnt/c/Users/{}", std::env::var("USER").unwrap())
+impl DbType {
+    /// Name used for databases that are not tied to a specific browser
+    pub fn name(&self) -> &'static str {
+        match self {
+            DbType::Chrome => "Chromium",
+            DbType::Firefox => "Firefox",
+            DbType::Safari => "Safari",
+            DbType::Unknown => "Unknown",
+        }
+    }
+}
+
+/// Returns the value of $HOME, unset if $HOME is empty or missing
+pub fn get_home() -> Option<String> {
+    std::env::var("HOME").ok().filter(|h| !h.is_empty())
+}
+
+/// Check if we are running under the Windows Subsystem for Linux
+fn is_wsl() -> bool {
+    std::env::var("WSL_DISTRO_NAME").is_ok()
+        || std::fs::read_to_string(WSL_OSRELEASE)
+            .is_ok_and(|r| r.to_lowercase().contains("microsoft"))
+}
+
+/// User directories under a `Users` or `home` directory, excluding
+/// the shared directories that Windows creates for all users
+fn user_dirs_under(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut user_dirs: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|e| {
+            !WINDOWS_SHARED_USER_DIRS
+                .contains(&e.file_name().to_string_lossy().as_ref())
+        })
+        .map(|e| e.path())
+        .collect();
+    user_dirs.sort();
+    user_dirs
+}
+
+/// The home directories to search for browser profiles.
+///
+/// If a `--root` is given, the root itself and every user directory
+/// below `home/`, `Users/` and `root/` of it are searched, this supports
+/// both copies of a single home directory and complete filesystems.
+/// Otherwise $HOME is searched, together with the Windows home directory
+/// of the current user under WSL (or all Windows home directories if it
+/// can not be determined).
+pub fn home_dirs() -> Vec<PathBuf> {
+    let mut homes = vec![];
+    if let Some(root) = &Config::global().root {
+        homes.extend(home_dirs_under_root(root));
     } else {
-        std::env::var("HOME").unwrap()
+        if let Some(home) = get_home() {
+            homes.push(PathBuf::from(home));
+        }
+        if is_wsl() {
+            let windows_users = Path::new(WSL_WINDOWS_USERS);
+            let windows_home = std::env::var("USER")
+                .ok()
+                .map(|user| windows_users.join(user))
+                .filter(|home| home.is_dir());
+            match windows_home {
+                Some(home) => homes.push(home),
+                None => homes.extend(user_dirs_under(windows_users)),
+            }
+        }
     }
+    homes.retain(|h| h.is_dir());
+    homes.dedup();
+    homes
+}
+
+/// The root itself and all user directories under it
+fn home_dirs_under_root(root: &Path) -> Vec<PathBuf> {
+    let mut homes = vec![root.to_owned()];
+    homes.extend(user_dirs_under(&root.join("home")));
+    homes.extend(user_dirs_under(&root.join("Users")));
+    homes.push(root.join("root"));
+    homes.retain(|h| h.is_dir());
+    homes
 }
 
 /// Check if a process is running using the `sysinfo` library
@@ -61,48 +144,17 @@ fn is_db_with_table(conn: &rusqlite::Connection, table_name: &str) -> bool {
         .is_ok();
 }
 
-/// Search all configured `SEARCH_DIRS` for SQLite databases and
-/// add each path to the provided set.
-pub fn cookie_dbs_from_profiles(cookie_dbs: &mut HashSet<CookieDB>) {
-    let home = get_home();
-    for search_dir in SEARCH_DIRS {
-        // 'home' needs to be cloned since it is referenced in each iteration
-        let search_path: String = format!("{}/{}", home.to_owned(), search_dir);
-
-        // We pass a reference of `search_path` since
-        // we want to retain ownership of the variable for later use
-        for entry in WalkDir::new(&search_path)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            // The filter is used to skip inaccessible paths
-            if entry.file_type().is_file()
-                && DB_NAMES
-                    .contains(&entry.file_name().to_string_lossy().as_ref())
-            {
-                let db_type =
-                    cookie_db_type(&(entry.path())).unwrap_or_else(|_| {
-                        return DbType::Unknown;
-                    });
-                if !matches!(db_type, DbType::Unknown) {
-                    cookie_dbs.insert(CookieDB {
-                        path: entry.into_path().to_owned(),
-                        typing: db_type,
-                        cookies: vec![],
-                    });
-                }
-            }
-        }
-    }
-}
-
 /// Finds all SQLite databases under the given path
-/// which feature a non-empty `cookies` or `moz_cookies` table
+/// which feature a non-empty `cookies` or `moz_cookies` table,
+/// and Safari cookie files
 pub fn cookie_db_type(filepath: &Path) -> Result<DbType, io::Error> {
     let mut f = File::open(filepath)?;
     let mut buf = [0; 15];
-    f.read_exact(&mut buf)?;
+    f.read_exact(&mut buf[..SAFARI_MAGIC.len()])?;
+    if buf.starts_with(SAFARI_MAGIC) {
+        return Ok(DbType::Safari);
+    }
+    f.read_exact(&mut buf[SAFARI_MAGIC.len()..])?;
 
     if let Ok(f_header) = String::from_utf8(buf.to_vec()) {
         if f_header != SQLITE_FILE_ID {
@@ -126,9 +178,48 @@ pub fn cookie_db_type(filepath: &Path) -> Result<DbType, io::Error> {
     return Ok(DbType::Unknown);
 }
 
+/// Expand glob patterns in `--file` arguments. Returns an error for the
+/// first argument that matches neither a pattern nor an existing file.
+pub fn expand_file_args(files: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut paths = vec![];
+    for file in files {
+        let matches: Vec<PathBuf> = glob::glob(file)
+            .map(|entries| entries.filter_map(|e| e.ok()).collect())
+            .unwrap_or_default();
+        if !matches.is_empty() {
+            paths.extend(matches);
+        } else if Path::new(file).exists() {
+            // Paths with characters such as '[' that are not valid patterns
+            paths.push(PathBuf::from(file));
+        } else {
+            return Err(format!("{file}: no such database"));
+        }
+    }
+    Ok(paths)
+}
+
+/// Print a debug message to stderr, or to `DEBUG_LOG` while the TUI
+/// has the terminal
+pub fn debug_log<T: std::fmt::Display>(msg: T) {
+    let cfg = Config::global();
+    if !cfg.debug {
+        return;
+    }
+    if cfg.tui {
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(DEBUG_LOG)
+            .expect("Failed to open debug log");
+
+        writeln!(f, "-> {msg}").expect("Failed to write debug message");
+    } else {
+        eprintln!("-> {msg}");
+    }
+}
+
 /// Parse the domains from a newline separated whitelist into a vector,
-/// skipping lines that start with '#'. Each entry will have explicit
-/// quotes surrounding it.
+/// skipping lines that start with '#'.
 pub fn parse_whitelist(filepath: &Path) -> Result<Vec<String>, io::Error> {
     let f = OpenOptions::new()
         .read(true)
@@ -142,14 +233,70 @@ pub fn parse_whitelist(filepath: &Path) -> Result<Vec<String>, io::Error> {
         // Skip comments
         let trimmed_line = line.trim();
         if !trimmed_line.starts_with("#") && trimmed_line.len() > 0 {
-            // Insert explicit qoutes
-            whitelist.push(format!("\"{trimmed_line}\""));
+            whitelist.push(trimmed_line.to_string());
         }
         line = "".to_string();
     }
     Ok(whitelist)
 }
 
+/// Decode `%XX` escapes in a string, invalid escapes are kept as is
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Encode all bytes except unreserved characters as `%XX`
+pub fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_'
+            | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// The domain that a host was registered under, e.g. `example.com` for
+/// `.www.example.com`. This is an approximation of the public suffix
+/// list that only knows the common second-level domains of country code
+/// TLDs like `co.uk`.
+pub fn registrable_domain(host: &str) -> String {
+    let host = host.trim_start_matches('.').to_lowercase();
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        return host;
+    }
+    let labels: Vec<&str> = host.split('.').collect();
+    let n = labels.len();
+    let keep = if n >= 3
+        && labels[n - 1].len() == 2
+        && COUNTRY_SECOND_LEVEL_DOMAINS.contains(&labels[n - 2])
+    {
+        3
+    } else {
+        2
+    };
+    labels[n.saturating_sub(keep)..].join(".")
+}
+
 /// Only applies if `SSH_CONNECTION` is unset.
 /// Utilises `xsel` on Linux/BSD.
 pub fn copy_to_clipboard(content: String) -> Result<(), io::Error> {
@@ -182,9 +329,29 @@ pub fn copy_to_clipboard(content: String) -> Result<(), io::Error> {
 
 #[cfg(test)]
 mod tests {
-    use crate::util::{cookie_db_type, DbType};
+    use crate::util::{
+        cookie_db_type, expand_file_args, home_dirs_under_root,
+        registrable_domain, DbType,
+    };
     use std::path::Path;
 
+    #[test]
+    fn test_home_dirs_under_root() {
+        let root = std::env::temp_dir().join("cookiecutter_test_root");
+        for dir in ["home/alice", "Users/bob", "Users/Public", "etc"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        assert_eq!(
+            home_dirs_under_root(&root),
+            vec![
+                root.to_owned(),
+                root.join("home/alice"),
+                root.join("Users/bob")
+            ]
+        );
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
     #[test]
     fn test_is_cookie_db() {
         if Path::new("moz_cookies.sqlite").exists() {
@@ -192,4 +359,39 @@ mod tests {
             assert!(matches!(result.unwrap(), DbType::Firefox));
         }
     }
+    #[test]
+    fn test_registrable_domain() {
+        assert_eq!(registrable_domain(".www.example.com"), "example.com");
+        assert_eq!(registrable_domain("shop.example.co.uk"), "example.co.uk");
+        assert_eq!(registrable_domain("localhost"), "localhost");
+        assert_eq!(registrable_domain("127.0.0.1"), "127.0.0.1");
+    }
+
+    #[test]
+    fn test_expand_file_args() {
+        let dir = std::env::temp_dir().join("cookiecutter_test_file_args");
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["a.sqlite", "b.sqlite", "[c].sqlite"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let arg = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        // "[c]" matches "c" as a pattern, but is kept as an existing file
+        assert_eq!(
+            expand_file_args(&[arg("*.sqlite"), arg("[c].sqlite")]).unwrap(),
+            [
+                dir.join("[c].sqlite"),
+                dir.join("a.sqlite"),
+                dir.join("b.sqlite"),
+                dir.join("[c].sqlite")
+            ]
+        );
+        for missing in [arg("*.db"), arg("missing.sqlite")] {
+            assert_eq!(
+                expand_file_args(std::slice::from_ref(&missing)).unwrap_err(),
+                format!("{missing}: no such database")
+            );
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Map, Value};
+
+use crate::config::Config;
+use crate::cookie::Cookie;
+use crate::cookie_db::CookieDB;
+use crate::diff::{diff_cookies, CookieChange};
+use crate::watcher::Reloader;
+
+/// How often to check for finished reloads
+const TICK: Duration = Duration::from_millis(100);
+
+/// A change as a single line of text, e.g.
+///     2024-05-01T12:00:00Z ~ .example.com sid Value: abc -> def
+fn change_line(
+    time: &str,
+    profile: Option<&str>,
+    change: &CookieChange,
+) -> String {
+    let c = change.cookie();
+    let mut line = time.to_string();
+    if let Some(profile) = profile {
+        line += &format!(" [{profile}]");
+    }
+    line += &format!(
+        " {} {} {}{}",
+        change.sign(),
+        c.host,
+        c.name,
+        change.details()
+    );
+    line
+}
+
+/// A change as a JSON object on a single line
+fn change_json(time: &str, cdb: &CookieDB, change: &CookieChange) -> String {
+    let c: &Cookie = change.cookie();
+    let mut event = json!({
+        "time": time,
+        "event": change.kind(),
+        "profile": cdb.display_name(),
+        "host": c.host,
+        "name": c.name,
+        "path": c.path,
+        "origin_attributes": c.origin_attributes,
+        "container": c.container,
+        "partition_key": c.partition_key(),
+        "value": c.match_field("Value", false),
+        "expiry": c.match_field("Expiry", false),
+    });
+    if let CookieChange::Changed { .. } = change {
+        let changes: Map<String, Value> = change
+            .changed_fields()
+            .into_iter()
+            .map(|(field, old, new)| {
+                (field.to_string(), json!({ "old": old, "new": new }))
+            })
+            .collect();
+        event["changes"] = Value::Object(changes);
+    }
+    event.to_string()
+}
+
+/// Print every change to the given databases until interrupted. The
+/// databases are reloaded whenever they are modified, and in addition
+/// every `--poll` seconds for filesystems without change notifications.
+/// The cookies of each database should already be loaded.
+pub fn watch(mut cookie_dbs: Vec<CookieDB>) {
+    let cfg = Config::global();
+    let mut reloader = Reloader::new(&cookie_dbs).unwrap_or_else(|err| {
+        eprintln!("WARN: Failed to watch databases: {err}");
+        Reloader::disabled()
+    });
+    let mut snapshots: Vec<Vec<Cookie>> =
+        cookie_dbs.iter().map(|c| c.cookies.clone()).collect();
+    let poll_interval = cfg.poll.map(Duration::from_secs);
+    let mut last_poll = Instant::now();
+
+    loop {
+        if poll_interval.is_some_and(|i| last_poll.elapsed() >= i) {
+            last_poll = Instant::now();
+            (0..cookie_dbs.len()).for_each(|i| reloader.request(i));
+        }
+        for i in reloader.poll(&mut cookie_dbs) {
+            let cdb = &cookie_dbs[i];
+            let time = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+            // Only name the profile if several profiles are watched
+            let profile = cdb.display_name();
+            let profile = (cookie_dbs.len() > 1).then_some(profile.as_str());
+
+            for change in diff_cookies(&snapshots[i], &cdb.cookies) {
+                if cfg.json {
+                    println!("{}", change_json(&time, cdb, &change));
+                } else {
+                    println!("{}", change_line(&time, profile, &change));
+                }
+            }
+            snapshots[i] = cdb.cookies.clone();
+        }
+        std::thread::sleep(TICK);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::config::{
+    Config, FIREFOX_SESSION_FILES, RELOAD_DELAY_MS, SQLITE_SIDE_FILES,
+};
+use crate::cookie::Cookie;
+use crate::cookie_db::CookieDB;
+use crate::error::CookieError;
+use crate::store::open_store;
+use crate::util::{debug_log, DbType};
+
+type ReloadResult = (usize, Result<Vec<Cookie>, CookieError>);
+
+/// Watches cookie databases (and their WAL and journal files, as well as
+/// the session files of Firefox profiles) for changes made by the browser
+/// and reloads loaded databases in the background. Databases are
+/// identified by their index in the vector that was passed to `new()`.
+pub struct Reloader {
+    /// Kept alive for as long as events should be received
+    _watcher: Option<RecommendedWatcher>,
+    changes: Receiver<usize>,
+    /// Databases with unhandled changes and the time of the last change
+    pending: HashMap<usize, Instant>,
+    /// Databases that are currently being reloaded
+    reloading: HashSet<usize>,
+    results_tx: Sender<ReloadResult>,
+    results: Receiver<ReloadResult>,
+}
+
+impl Reloader {
+    /// Start watching the directories of all databases, directories
+    /// that can not be watched are skipped
+    pub fn new(cookie_dbs: &[CookieDB]) -> Result<Self, notify::Error> {
+        let (changes_tx, changes) = channel();
+        let (results_tx, results) = channel();
+
+        // The files that belong to each database, SQLite replaces
+        // `-journal` files on each transaction so the parent directories
+        // are watched rather than the files themselves
+        let mut files: HashMap<PathBuf, usize> = HashMap::new();
+        let mut dirs: HashSet<PathBuf> = HashSet::new();
+        for (i, cdb) in cookie_dbs.iter().enumerate() {
+            // Events are reported with absolute paths
+            let Ok(path) = std::fs::canonicalize(&cdb.path) else {
+                continue;
+            };
+            for suffix in SQLITE_SIDE_FILES {
+                let mut side_file = path.clone().into_os_string();
+                side_file.push(suffix);
+                files.insert(PathBuf::from(side_file), i);
+            }
+            if let Some(dir) = path.parent() {
+                dirs.insert(dir.to_owned());
+                // Session cookies are read from the session files,
+                // the recovery file is kept in a subdirectory
+                if cdb.typing == DbType::Firefox {
+                    for session_file in FIREFOX_SESSION_FILES {
+                        let session_file = dir.join(session_file);
+                        if let Some(session_dir) = session_file.parent() {
+                            dirs.insert(session_dir.to_owned());
+                        }
+                        files.insert(session_file, i);
+                    }
+                }
+            }
+            files.insert(path, i);
+        }
+
+        let mut watcher = notify::recommended_watcher(
+            move |res: notify::Result<notify::Event>| {
+                let Ok(event) = res else {
+                    return;
+                };
+                // Access events are skipped, they are also
+                // emitted when the database is read for a reload
+                if !matches!(
+                    event.kind,
+                    EventKind::Create(_)
+                        | EventKind::Modify(_)
+                        | EventKind::Remove(_)
+                ) {
+                    return;
+                }
+                for path in event.paths {
+                    if let Some(i) = files.get(&path) {
+                        let _ = changes_tx.send(*i);
+                    }
+                }
+            },
+        )?;
+        for dir in dirs {
+            if let Err(err) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                debug_log(format!("Failed to watch {}: {err}", dir.display()));
+            }
+        }
+
+        Ok(Reloader {
+            _watcher: Some(watcher),
+            changes,
+            pending: HashMap::new(),
+            reloading: HashSet::new(),
+            results_tx,
+            results,
+        })
+    }
+
+    /// A reloader that never reports any changes
+    pub fn disabled() -> Self {
+        let (results_tx, results) = channel();
+        Reloader {
+            _watcher: None,
+            changes: channel().1,
+            pending: HashMap::new(),
+            reloading: HashSet::new(),
+            results_tx,
+            results,
+        }
+    }
+
+    /// Reload a database as if it had been modified
+    pub fn request(&mut self, i: usize) {
+        self.pending.insert(i, Instant::now());
+    }
+
+    /// Start a background reload of each loaded database that has not
+    /// changed for `RELOAD_DELAY_MS`, a browser usually writes several times
+    /// in a row. Finished reloads replace the cookies of the database and
+    /// the indices of all replaced databases are returned.
+    pub fn poll(&mut self, cookie_dbs: &mut [CookieDB]) -> Vec<usize> {
+        let now = Instant::now();
+        for i in self.changes.try_iter() {
+            self.pending.insert(i, now);
+        }
+
+        let delay = Duration::from_millis(RELOAD_DELAY_MS);
+        let ready: Vec<usize> = self
+            .pending
+            .iter()
+            .filter(|(i, t)| {
+                now.duration_since(**t) >= delay && !self.reloading.contains(i)
+            })
+            .map(|(i, _)| *i)
+            .collect();
+        for i in ready {
+            self.pending.remove(&i);
+            // Databases that have not been loaded yet will
+            // be read from scratch once they are selected
+            let Some(cdb) = cookie_dbs.get(i).filter(|c| c.loaded) else {
+                continue;
+            };
+            let typing = cdb.typing.clone();
+            let path = cdb.path.clone();
+            let browser = cdb.browser.clone();
+            let results_tx = self.results_tx.clone();
+            self.reloading.insert(i);
+            std::thread::spawn(move || {
+                let result = open_store(&typing, &path, &browser)
+                    .and_then(|store| store.load());
+                let _ = results_tx.send((i, result));
+            });
+        }
+
+        let mut updated = vec![];
+        for (i, result) in self.results.try_iter() {
+            self.reloading.remove(&i);
+            let Some(cdb) = cookie_dbs.get_mut(i) else {
+                continue;
+            };
+            match result {
+                Ok(cookies) => {
+                    cdb.set_cookies(cookies);
+                    cdb.retain_matching(&Config::global().filter);
+                    updated.push(i);
+                }
+                // The database can be in the middle of being rewritten,
+                // the next change will trigger another reload
+                Err(err) => debug_log(format!(
+                    "Failed to reload {}: {err}",
+                    cdb.path_short()
+                )),
+            }
+        }
+        updated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::cookie_db::CookieDB;
+    use crate::firefox::tests::create_firefox_db;
+    use crate::util::DbType;
+    use crate::watcher::Reloader;
+
+    #[test]
+    fn test_watch_session_file() {
+        let path = create_firefox_db("cookiecutter_test_watcher");
+        let session_dir = path.with_file_name("sessionstore-backups");
+        std::fs::create_dir_all(&session_dir).unwrap();
+        let cdb = CookieDB::new(path.clone(), DbType::Firefox, "Firefox");
+        let reloader = Reloader::new(&[cdb]).unwrap();
+
+        std::fs::write(session_dir.join("recovery.jsonlz4"), b"").unwrap();
+        let changed = reloader.changes.recv_timeout(Duration::from_secs(5));
+        assert_eq!(changed, Ok(0));
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}